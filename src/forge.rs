@@ -1,54 +1,411 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use attohttpc::Session;
 use chrono::{DateTime, Utc};
-use semver::Version;
+use rayon::prelude::*;
+use regex::Regex;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt,
     path::Path,
-    time::{SystemTime, UNIX_EPOCH},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tracing::{debug, instrument, warn};
+use ureq::{Agent, AgentBuilder};
+
+/// The public Forge-API, asked unless another one is given
+pub const DEFAULT_FORGE_URL: &str = "https://forgeapi.puppet.com";
+/// Website of the public Forge, where the modules have their pages
+const FORGE_WEBSITE: &str = "https://forge.puppet.com";
+/// How long a module's data is used before asking the Forge again, in seconds
+const CACHE_TTL: u64 = 60 * 60;
+/// How long to remember that a module does not exist, in seconds. Shorter than `CACHE_TTL` so a
+/// newly published module shows up soon.
+const NOT_FOUND_TTL: u64 = 10 * 60;
+/// How often a request is sent if the Forge answers that there were too many (HTTP 429)
+const MAX_ATTEMPTS: u32 = 3;
+/// How long to wait after HTTP 429 if the Forge does not say (`Retry-After`)
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+/// Longest wait the Forge may ask for after HTTP 429, beyond that the lookup fails
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Fields of the Forge's answers that make them large and are only needed for some lookups, see
+/// `exclude_fields`
+const LARGE_FIELDS: [&str; 8] = [
+    "readme",
+    "changelog",
+    "license",
+    "reference",
+    "tasks",
+    "plans",
+    "metadata",
+    "tags",
+];
+
+/// Errors when looking up a module on the Forge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForgeError {
+    /// The module (or release) does not exist on the Forge, e.g. because it is internal-only or
+    /// was removed
+    NotFound(String),
+    /// The Forge could not be reached or answered with an error
+    Communication(String),
+    /// The Forge's answer could not be understood
+    InvalidResponse(String),
+    /// The module exists on the Forge but has not published a release yet, e.g. a freshly
+    /// claimed namespace
+    NoReleases(String),
+}
+
+impl fmt::Display for ForgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForgeError::NotFound(name) => write!(f, "{name} was not found on the Forge"),
+            ForgeError::Communication(e) => write!(f, "Failure in communication with forge: {e}"),
+            ForgeError::InvalidResponse(e) => write!(f, "Invalid answer from forge: {e}"),
+            ForgeError::NoReleases(name) => write!(f, "{name} has no releases on the Forge"),
+        }
+    }
+}
+
+impl std::error::Error for ForgeError {}
+
+impl ForgeError {
+    /// Converts an error of a request for `name`
+    fn from_request(name: &str, e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(404, _) => ForgeError::NotFound(name.to_owned()),
+            e => ForgeError::Communication(e.to_string()),
+        }
+    }
+}
 
 #[derive(Debug, Deserialize)]
 struct ForgeResponse {
-    current_release: ForgeCurrentRelease,
+    /// Missing if the module has no releases
+    current_release: Option<ForgeCurrentRelease>,
     deprecated_at: Option<DateTime<Utc>>,
+    deprecated_for: Option<String>,
+    /// The module taking the place of a deprecated one, if its author named one
+    superseded_by: Option<ForgeModuleRef>,
+    #[serde(default)]
+    releases: Vec<ForgeRelease>,
+    endorsement: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+struct ForgeModuleRef {
+    slug: String,
 }
 #[derive(Debug, Deserialize)]
 struct ForgeCurrentRelease {
     version: String,
+    /// Only there if asked for, see `fetch_fields`
+    metadata: Option<ReleaseMetadata>,
+}
+#[derive(Debug, Deserialize)]
+struct ForgeRelease {
+    version: String,
+    created_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForgeReleaseResponse {
+    metadata: ReleaseMetadata,
+}
+
+/// The parts of a release's `metadata.json` that are looked at
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseMetadata {
+    /// Modules the release needs
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+    /// Versions of Puppet (or Bolt, …) the release works with, same shape as the dependencies
+    #[serde(default)]
+    pub requirements: Vec<Dependency>,
+    /// Operating systems the release claims to support
+    #[serde(default)]
+    pub operatingsystem_support: Vec<OsSupport>,
+}
+
+/// An operating system a release claims to support, as declared in its `metadata.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct OsSupport {
+    /// Name as reported by Facter, e.g. `RedHat` or `Ubuntu`
+    pub operatingsystem: String,
+    /// Supported releases like `9` or `22.04`, all of them if empty
+    #[serde(default)]
+    pub operatingsystemrelease: Vec<String>,
+}
+
+impl OsSupport {
+    /// Returns whether this covers release `release` of the operating system `name` (any release
+    /// if `None`). Names are compared case-insensitively, and a major release like `9` covers
+    /// `9.2`.
+    pub fn covers(&self, name: &str, release: Option<&str>) -> bool {
+        if !self.operatingsystem.eq_ignore_ascii_case(name) {
+            return false;
+        }
+        let Some(release) = release else {
+            return true;
+        };
+        self.operatingsystemrelease.is_empty()
+            || self.operatingsystemrelease.iter().any(|supported| {
+                release == supported
+                    || release
+                        .strip_prefix(supported.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+    }
+}
+
+/// A dependency or requirement of a release as declared in its `metadata.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+    /// Name of the module, normalized to `author-module`
+    pub name: String,
+    /// Puppet-style requirement, e.g. `>= 4.13.1 < 10.0.0`. A missing requirement allows any
+    /// version.
+    pub version_requirement: Option<String>,
 }
 
+/// Information about a single module as stored in the cache
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CacheEntry {
+    /// Version of the current release, which may be a pre-release. Empty if there is none.
     pub version: String,
     pub is_deprecated: bool,
     pub time_fetched: u64,
+    /// All releases that have not been deleted, newest first
+    #[serde(default)]
+    pub releases: Vec<ReleaseEntry>,
+    /// Endorsement by Puppet (`supported`, `approved` or `partner`), if any
+    #[serde(default)]
+    pub endorsement: Option<String>,
+    /// The module does not exist on the Forge, all other fields are empty
+    #[serde(default)]
+    pub not_found: bool,
+    /// When the module was deprecated, if it is
+    #[serde(default)]
+    pub deprecated_at: Option<DateTime<Utc>>,
+    /// Why the module was deprecated, as given by its author
+    #[serde(default)]
+    pub deprecated_for: Option<String>,
+    /// The module replacing this one if it is deprecated, as `author-module`
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    /// Forge-API the entry was fetched from. Entries of another Forge are not used, a mirror may
+    /// serve another module of the same name. Entries cached by older versions came from the
+    /// public Forge.
+    #[serde(default = "default_forge_url")]
+    pub forge: String,
+}
+
+fn default_forge_url() -> String {
+    DEFAULT_FORGE_URL.to_string()
+}
+
+/// Details about the deprecation of a module
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    /// When the module was deprecated. Unknown for entries cached by older versions.
+    pub at: Option<DateTime<Utc>>,
+    /// Why the module was deprecated, often naming the replacement
+    pub reason: Option<String>,
+    /// The module to use instead as `author-module`, if the Forge or the reason names one
+    pub replacement: Option<String>,
+}
+
+/// What most views need to know about a module, looked up at once by `get_entry`
+#[derive(Debug, Clone)]
+pub struct ModuleEntry {
+    /// The latest version as returned by `get_version`, `None` if the module has no releases
+    pub version: Option<Version>,
+    /// `None` if the module is not deprecated
+    pub deprecation: Option<Deprecation>,
 }
 
+/// A single release of a module
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReleaseEntry {
+    pub version: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Data fetched from the Forge for a single module
+struct FetchedModule {
+    /// `None` if the module has no releases
+    version: Option<Version>,
+    deprecated_at: Option<DateTime<Utc>>,
+    deprecated_for: Option<String>,
+    superseded_by: Option<String>,
+    releases: Vec<ReleaseEntry>,
+    endorsement: Option<String>,
+}
+
+/// A Forge-API to talk to and how to authenticate there
+#[derive(Debug)]
+struct Endpoint {
+    /// URL of the Forge-API without a trailing slash, e.g. `https://forgeapi.puppet.com`
+    base_url: String,
+    /// Value of the `Authorization` header sent with each request, if there is a token
+    authorization: Option<String>,
+}
+
+impl Endpoint {
+    /// Without a `token`, the password of the host's entry in `~/.netrc` is used if there is one
+    fn new(base_url: &str, token: Option<String>) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        let token = token.or_else(|| {
+            let path = shellexpand::tilde("~/.netrc");
+            let contents = std::fs::read_to_string(path.as_ref()).ok()?;
+            let password = netrc_password(&contents, url_host(&base_url));
+            if password.is_some() {
+                debug!(
+                    "Using the credentials for {} from {path}",
+                    url_host(&base_url)
+                );
+            }
+            password
+        });
+        Self {
+            base_url,
+            authorization: token.map(|token| format!("Bearer {token}")),
+        }
+    }
+}
+
+/// A Forge serving the modules of an author instead of the default one, e.g. an internal mirror
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorForge {
+    /// The author as in `author-module`
+    pub author: String,
+    /// URL of the Forge-API
+    pub url: String,
+    /// Sent as bearer token, `~/.netrc` is looked at if there is none
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// Contents of the file given with `--forge-config`, in TOML:
+///
+/// ```toml
+/// [[forge]]
+/// author = "mycompany"
+/// url = "https://forge.example.com"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForgeConfig {
+    #[serde(default)]
+    pub forge: Vec<AuthorForge>,
+}
+
+impl ForgeConfig {
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+/// Wrapper around the Forge-API with a crude cache implementation. All lookups take `&self`, so a
+/// single instance can be shared between threads.
 #[derive(Debug)]
 pub struct ForgeApi {
-    session: Session,
-    cache: HashMap<String, CacheEntry>,
+    /// Keeps the connection to the Forge alive between requests, so the TLS handshake is done only
+    /// once instead of once per module
+    agent: Agent,
+    /// The Forge asked about all modules, except for the authors in `author_forges`
+    forge: Endpoint,
+    /// Forges asked about the modules of an author instead, see `add_author_forge`
+    author_forges: HashMap<String, Endpoint>,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+    /// Modules whose cache entry has been checked (and refreshed if needed) during this run
+    fetched_this_run: Mutex<HashSet<String>>,
+    /// Names that turned out not to exist during this run, so they are counted in `cache_stats`
+    /// only once
+    missing_this_run: Mutex<HashSet<String>>,
+    /// Lookups answered by the cache and the ones that asked the Forge, see `cache_stats`
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize,
+    /// Whether pre-releases count as the latest version
+    include_prereleases: bool,
+    /// The `LARGE_FIELDS` asked for when looking up a module, see `fetch_fields`
+    fetch_fields: Vec<&'static str>,
+    /// Answer everything from the cache as it was loaded, see `use_snapshot`
+    snapshot: bool,
+    /// Metadata of releases by `author-module-version`. Releases don't change, but they are
+    /// only needed by some views, so they are kept for this run only.
+    release_metadata: Mutex<HashMap<String, ReleaseMetadata>>,
 }
 
-/// Wrapper around the Forge-API with a crude cache implementation
 impl ForgeApi {
-    pub fn new(cache_file: Option<String>) -> Self {
-        let mut session = Session::new();
-        session.follow_redirects(false);
+    /// Creates a new instance talking to the Forge-API at `base_url`, optionally loading the cache
+    /// from `cache_file`. Without a `token`, the password of the forge host's entry in `~/.netrc`
+    /// is used if there is one.
+    pub fn new(cache_file: Option<String>, base_url: &str, token: Option<String>) -> Self {
+        let agent = AgentBuilder::new().redirects(0).build();
         Self {
-            session,
-            cache: match cache_file {
+            agent,
+            forge: Endpoint::new(base_url, token),
+            author_forges: HashMap::new(),
+            cache: Mutex::new(match cache_file {
                 Some(f) => Self::load_cache(f),
                 None => HashMap::new(),
-            },
+            }),
+            fetched_this_run: Mutex::new(HashSet::new()),
+            missing_this_run: Mutex::new(HashSet::new()),
+            cache_hits: AtomicUsize::new(0),
+            cache_misses: AtomicUsize::new(0),
+            include_prereleases: false,
+            fetch_fields: vec![],
+            snapshot: false,
+            release_metadata: Mutex::new(HashMap::new()),
         }
     }
 
-    fn load_cache<P: AsRef<Path> + std::fmt::Debug>(cache_file: P) -> HashMap<String, CacheEntry> {
+    /// Looks up the modules of `author` on the Forge-API at `base_url` instead of the default one,
+    /// authenticating like `new` does
+    pub fn add_author_forge(&mut self, author: &str, base_url: &str, token: Option<String>) {
+        self.author_forges
+            .insert(author.to_owned(), Endpoint::new(base_url, token));
+    }
+
+    /// The Forge to ask about `name`, which is a module (`author-module` or `author/module`) or
+    /// a release (`author-module-version`)
+    fn endpoint(&self, name: &str) -> &Endpoint {
+        let author = name.split(['-', '/']).next().unwrap_or_default();
+        self.author_forges.get(author).unwrap_or(&self.forge)
+    }
+
+    /// Sets whether a pre-release can be the latest version of a module. If not (the default),
+    /// the latest stable release is used.
+    pub fn include_prereleases(&mut self, include: bool) {
+        self.include_prereleases = include;
+    }
+
+    /// Asks for these of the `LARGE_FIELDS` as well when looking up a module (e.g. `metadata`), for
+    /// the views that need more than the versions and the deprecation. Left out by default to keep
+    /// the answers small. With `metadata`, the metadata of the current release comes along and
+    /// `get_release_metadata` does not need to ask for it.
+    pub fn fetch_fields(&mut self, fields: &[&'static str]) {
+        self.fetch_fields = fields.to_vec();
+    }
+
+    /// Answers all lookups from the loaded cache regardless of its age and never asks the Forge,
+    /// so the results are reproducible. Modules missing from the cache are treated as not found.
+    pub fn use_snapshot(&mut self) {
+        self.snapshot = true;
+    }
+
+    /// Reads the cache from `cache_file`. A missing or broken cache is treated as an empty one.
+    pub fn load_cache<P: AsRef<Path> + std::fmt::Debug>(
+        cache_file: P,
+    ) -> HashMap<String, CacheEntry> {
         debug!("Loading cache from {cache_file:?}");
         let data = match std::fs::read_to_string(&cache_file) {
             Ok(d) => d,
@@ -61,72 +418,858 @@ impl ForgeApi {
             Ok(d) => d,
             Err(e) => {
                 warn!("Cache parsing failed: {e}");
-                return HashMap::new();
+                HashMap::new()
             }
         }
     }
-    pub fn store_cache<P: AsRef<Path> + std::fmt::Debug>(&self, cache_file: P) {
+
+    /// Writes the cache to `cache_file`
+    pub fn store_cache<P: AsRef<Path> + std::fmt::Debug>(
+        &self,
+        cache_file: P,
+    ) -> std::io::Result<()> {
         debug!("Writing cache to {cache_file:?}");
-        std::fs::write(cache_file, serde_json::to_string(&self.cache).unwrap()).unwrap();
+        let cache = self.cache.lock().unwrap();
+        std::fs::write(cache_file, serde_json::to_string(&*cache)?)
     }
 
+    /// Looks up the modules `names` concurrently, with at most `jobs` requests in flight, so the
+    /// lookups that follow are answered from the cache. Failures are left to these lookups, which
+    /// try again.
+    pub fn prefetch(&self, names: &[String], jobs: usize) {
+        let pool = match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("Could not start the threads for looking up modules: {e}");
+                return;
+            }
+        };
+        pool.install(|| {
+            names.par_iter().for_each(|name| {
+                if let Err(e) = self.get_data(name) {
+                    debug!("Prefetching {name} failed: {e}");
+                }
+            })
+        });
+    }
+
+    /// Returns the latest version and the deprecation of the module `name` with a single lookup,
+    /// for the views needing both
     #[instrument(skip(self))]
-    pub fn get_version(&mut self, name: &str) -> Result<Version, String> {
-        self.get_data(name)?;
-        Ok(Version::parse(&self.cache.get(name).unwrap().version).unwrap())
+    pub fn get_entry(&self, name: &str) -> Result<ModuleEntry, ForgeError> {
+        self.with_entry(name, |entry| ModuleEntry {
+            version: self.latest_version(entry),
+            deprecation: deprecation(entry),
+        })
     }
 
+    /// Returns the latest version of the module `name`, `ForgeError::NoReleases` if there is none
     #[instrument(skip(self))]
-    pub fn is_deprecated(&mut self, name: &str) -> Result<bool, String> {
+    pub fn get_version(&self, name: &str) -> Result<Version, ForgeError> {
+        self.with_entry(name, |entry| self.latest_version(entry))?
+            .ok_or_else(|| ForgeError::NoReleases(name.to_owned()))
+    }
+
+    /// The latest version in `entry`, see `include_prereleases`
+    fn latest_version(&self, entry: &CacheEntry) -> Option<Version> {
+        if entry.version.is_empty() {
+            return None;
+        }
+        // the cache may come from elsewhere (`--snapshot`), don't trust it
+        let Ok(current) = Version::parse(&entry.version) else {
+            warn!(
+                "Cached version {} is not semver-compatible, ignoring it",
+                entry.version
+            );
+            return None;
+        };
+        if self.include_prereleases || current.pre.is_empty() {
+            return Some(current);
+        }
+        // entries cached by older versions have no releases, stick to the current release then
+        Some(
+            entry
+                .releases
+                .iter()
+                .filter_map(|release| Version::parse(&release.version).ok())
+                .filter(|version| version.pre.is_empty())
+                .max()
+                .unwrap_or(current),
+        )
+    }
+
+    /// Returns whether the module `name` is marked as deprecated
+    #[instrument(skip(self))]
+    pub fn is_deprecated(&self, name: &str) -> Result<bool, ForgeError> {
+        self.with_entry(name, |entry| entry.is_deprecated)
+    }
+
+    /// Returns the details of the deprecation of the module `name`, `None` if it is not deprecated
+    #[instrument(skip(self))]
+    pub fn get_deprecation(&self, name: &str) -> Result<Option<Deprecation>, ForgeError> {
+        self.with_entry(name, deprecation)
+    }
+
+    /// Returns the endorsement (`supported`, `approved` or `partner`) of the module `name`
+    #[instrument(skip(self))]
+    pub fn get_endorsement(&self, name: &str) -> Result<Option<String>, ForgeError> {
+        self.with_entry(name, |entry| entry.endorsement.clone())
+    }
+
+    /// Returns when the latest version (as returned by `get_version`) of the module `name` was
+    /// released, if known
+    #[instrument(skip(self))]
+    pub fn get_release_date(&self, name: &str) -> Result<Option<DateTime<Utc>>, ForgeError> {
+        let version = self.get_version(name)?;
+        self.with_entry(name, |entry| {
+            entry
+                .releases
+                .iter()
+                .find(|release| Version::parse(&release.version).ok().as_ref() == Some(&version))
+                .and_then(|release| release.created_at)
+        })
+    }
+
+    /// Returns all releases of the module `name` that were not deleted, newest first
+    #[instrument(skip(self))]
+    pub fn get_releases(&self, name: &str) -> Result<Vec<ReleaseEntry>, ForgeError> {
+        self.with_entry(name, |entry| entry.releases.clone())
+    }
+
+    /// Returns the dependencies of release `version` of the module `name`
+    #[instrument(skip(self))]
+    pub fn get_dependencies(
+        &self,
+        name: &str,
+        version: &Version,
+    ) -> Result<Vec<Dependency>, ForgeError> {
+        Ok(self.get_release_metadata(name, version)?.dependencies)
+    }
+
+    /// Returns the metadata of release `version` of the module `name`
+    #[instrument(skip(self))]
+    pub fn get_release_metadata(
+        &self,
+        name: &str,
+        version: &Version,
+    ) -> Result<ReleaseMetadata, ForgeError> {
+        let slug = format!("{}-{version}", name.replace("/", "-"));
+        if let Some(metadata) = self.release_metadata.lock().unwrap().get(&slug) {
+            return Ok(metadata.clone());
+        }
+        if self.snapshot {
+            return Err(ForgeError::Communication(format!(
+                "the metadata of {slug} is not part of the snapshot"
+            )));
+        }
+        let url = &format!(
+            "{}/v3/releases/{slug}?{}",
+            self.endpoint(&slug).base_url,
+            exclude_fields(&["metadata"])
+        );
+        debug!("Fetching {url}");
+        let metadata = self
+            .call(url, &slug)?
+            .into_json::<ForgeReleaseResponse>()
+            .map_err(|e| ForgeError::InvalidResponse(e.to_string()))?
+            .metadata;
+        Ok(self.remember_metadata(slug, metadata))
+    }
+
+    /// Keeps the `metadata` of the release `slug` for this run
+    fn remember_metadata(&self, slug: String, mut metadata: ReleaseMetadata) -> ReleaseMetadata {
+        for dependency in &mut metadata.dependencies {
+            dependency.name = dependency.name.replace("/", "-");
+        }
+        self.release_metadata
+            .lock()
+            .unwrap()
+            .insert(slug, metadata.clone());
+        metadata
+    }
+
+    /// Makes sure the cache entry of `name` is up to date and passes it to `f`
+    fn with_entry<T>(&self, name: &str, f: impl FnOnce(&CacheEntry) -> T) -> Result<T, ForgeError> {
         self.get_data(name)?;
-        Ok(self.cache.get(name).unwrap().is_deprecated)
+        Ok(f(self.cache.lock().unwrap().get(name).unwrap()))
     }
 
-    fn get_data(&mut self, name: &str) -> Result<(), String> {
+    fn get_data(&self, name: &str) -> Result<(), ForgeError> {
+        if self.fetched_this_run.lock().unwrap().contains(name) {
+            return Ok(());
+        }
+        if self.missing_this_run.lock().unwrap().contains(name) {
+            return Err(ForgeError::NotFound(name.to_owned()));
+        }
+        let forge = &self.endpoint(name).base_url;
+        if self.snapshot {
+            return match self.cache.lock().unwrap().get(name) {
+                Some(e) if !e.not_found && e.forge == *forge => Ok(()),
+                _ => Err(ForgeError::NotFound(name.to_owned())),
+            };
+        }
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        if let Some(e) = self.cache.get(name) {
-            if e.time_fetched < now - 60 * 60 {
-                debug!("Value in cache and outdated");
-                let (version, deprecated_at) = self.fetch_data(name)?;
-                let e = self.cache.get_mut(name).unwrap();
-                e.version = version.to_string();
-                e.is_deprecated = deprecated_at.is_some();
-                e.time_fetched = now;
-            } else {
+        let up_to_date = match self.cache.lock().unwrap().get(name) {
+            Some(e) if e.forge != *forge => {
+                debug!("Value in cache is from another Forge");
+                false
+            }
+            Some(e) if e.not_found && e.time_fetched >= now - NOT_FOUND_TTL => {
+                debug!("Known to be missing");
+                self.cache_hits.fetch_add(1, Ordering::Relaxed);
+                self.missing_this_run
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_owned());
+                return Err(ForgeError::NotFound(name.to_owned()));
+            }
+            Some(e) if !e.not_found && e.time_fetched >= now - CACHE_TTL => {
                 debug!("Value in cache");
+                true
             }
+            Some(_) => {
+                debug!("Value in cache and outdated");
+                false
+            }
+            None => {
+                debug!("Value not in cache");
+                false
+            }
+        };
+        if up_to_date {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
         } else {
-            debug!("Value not in cache");
-            let (version, deprecated_at) = self.fetch_data(name)?;
-            self.cache.insert(
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+            // the cache is not locked while talking to the Forge, so other threads can go on
+            let fetched = match self.fetch_data(name) {
+                Ok(f) => f,
+                Err(ForgeError::NotFound(missing)) => {
+                    // remember it for a while so it isn't asked for on every run
+                    self.cache.lock().unwrap().insert(
+                        name.to_owned(),
+                        CacheEntry {
+                            version: String::new(),
+                            is_deprecated: false,
+                            time_fetched: now,
+                            releases: vec![],
+                            endorsement: None,
+                            not_found: true,
+                            deprecated_at: None,
+                            deprecated_for: None,
+                            superseded_by: None,
+                            forge: forge.clone(),
+                        },
+                    );
+                    self.missing_this_run
+                        .lock()
+                        .unwrap()
+                        .insert(name.to_owned());
+                    return Err(ForgeError::NotFound(missing));
+                }
+                Err(e) => return Err(e),
+            };
+            self.cache.lock().unwrap().insert(
                 name.to_owned(),
                 CacheEntry {
-                    version: version.to_string(),
-                    is_deprecated: deprecated_at.is_some(),
+                    version: fetched
+                        .version
+                        .map(|version| version.to_string())
+                        .unwrap_or_default(),
+                    is_deprecated: fetched.deprecated_at.is_some(),
                     time_fetched: now,
+                    releases: fetched.releases,
+                    endorsement: fetched.endorsement,
+                    not_found: false,
+                    deprecated_at: fetched.deprecated_at,
+                    deprecated_for: fetched.deprecated_for,
+                    superseded_by: fetched.superseded_by,
+                    forge: forge.clone(),
                 },
             );
         }
+        self.fetched_this_run
+            .lock()
+            .unwrap()
+            .insert(name.to_owned());
         Ok(())
     }
 
-    fn fetch_data(&self, name: &str) -> Result<(Version, Option<DateTime<Utc>>), String> {
+    /// Returns how many modules were looked up in the cache and how many had to be fetched from
+    /// the Forge, as (hits, misses). A snapshot is not counted.
+    pub fn cache_stats(&self) -> (usize, usize) {
+        (
+            self.cache_hits.load(Ordering::Relaxed),
+            self.cache_misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns the page of the module `name` on the website of its Forge. Only the public Forge
+    /// is known to have one.
+    pub fn web_url(&self, name: &str) -> Option<String> {
+        (self.endpoint(name).base_url == DEFAULT_FORGE_URL)
+            .then(|| format!("{FORGE_WEBSITE}/modules/{}", name.replacen('-', "/", 1)))
+    }
+
+    /// Returns the URL that is requested to look up the module `name`
+    pub fn module_url(&self, name: &str) -> String {
         let name = name.replace("/", "-");
-        let url = &format!("https://forgeapi.puppet.com/v3/modules/{}?exclude_fields=readme,changelog,license,reference,tasks,plans,metadata,tags", name);
+        format!(
+            "{}/v3/modules/{name}?{}",
+            self.endpoint(&name).base_url,
+            exclude_fields(&self.fetch_fields)
+        )
+    }
+
+    /// Sends a single `HEAD` request to the base URL of the default Forge and returns the status
+    /// it was answered with. Any answer counts, even an error status, as the Forge can be reached
+    /// then.
+    pub fn ping(&self) -> Result<u16, ForgeError> {
+        let mut request = self.agent.head(&self.forge.base_url);
+        if let Some(ref authorization) = self.forge.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        match request.call() {
+            Ok(res) => Ok(res.status()),
+            Err(ureq::Error::Status(status, _)) => Ok(status),
+            Err(e) => Err(ForgeError::Communication(e.to_string())),
+        }
+    }
+
+    /// Requests `url` to look up `name`. If the Forge answers that there were too many requests,
+    /// waits as long as it asks for (at most `MAX_RETRY_AFTER`) and tries again, up to
+    /// `MAX_ATTEMPTS` times.
+    fn call(&self, url: &str, name: &str) -> Result<ureq::Response, ForgeError> {
+        let mut attempt = 1;
+        loop {
+            let mut request = self.agent.get(url);
+            if let Some(ref authorization) = self.endpoint(name).authorization {
+                request = request.set("Authorization", authorization);
+            }
+            match request.call() {
+                Err(ureq::Error::Status(429, res)) if attempt < MAX_ATTEMPTS => {
+                    let wait = res
+                        .header("Retry-After")
+                        .and_then(|value| parse_retry_after(value, Utc::now()))
+                        .unwrap_or(DEFAULT_RETRY_AFTER);
+                    if wait > MAX_RETRY_AFTER {
+                        return Err(ForgeError::Communication(format!(
+                            "too many requests, the Forge asks to wait {wait:?} before retrying {name}"
+                        )));
+                    }
+                    warn!("Too many requests to the Forge, retrying {name} in {wait:?}");
+                    std::thread::sleep(wait);
+                    attempt += 1;
+                }
+                result => return result.map_err(|e| ForgeError::from_request(name, e)),
+            }
+        }
+    }
+
+    fn fetch_data(&self, name: &str) -> Result<FetchedModule, ForgeError> {
+        let url = &self.module_url(name);
         debug!("Fetching {url}");
 
+        // the connection is only returned to the pool once the body has been read completely,
+        // which `into_json` does
         let res: ForgeResponse = self
-            .session
-            .get(url)
-            .send()
-            .map_err(|e| format!("Failure in communication with forge: {e}"))?
-            .json()
-            .map_err(|_| "Failed to parse forge json")?;
-        let version = Version::parse(&res.current_release.version)
-            .map_err(|e| format!("Returned version is not semver-compatible: {e}"))?;
-        Ok((version, res.deprecated_at))
+            .call(url, name)?
+            .into_json()
+            .map_err(|e| ForgeError::InvalidResponse(e.to_string()))?;
+        let version = res
+            .current_release
+            .map(|release| {
+                let version = Version::parse(&release.version)?;
+                if let Some(metadata) = release.metadata {
+                    self.remember_metadata(
+                        format!("{}-{version}", name.replace("/", "-")),
+                        metadata,
+                    );
+                }
+                Ok(version)
+            })
+            .transpose()
+            .map_err(|e: semver::Error| {
+                ForgeError::InvalidResponse(format!("version is not semver-compatible: {e}"))
+            })?;
+        let mut releases: Vec<ReleaseEntry> = res
+            .releases
+            .into_iter()
+            .filter(|release| release.deleted_at.is_none())
+            .map(|release| ReleaseEntry {
+                version: release.version,
+                created_at: release.created_at,
+            })
+            .collect();
+        releases
+            .sort_by_cached_key(|release| std::cmp::Reverse(Version::parse(&release.version).ok()));
+        Ok(FetchedModule {
+            version,
+            deprecated_at: res.deprecated_at,
+            deprecated_for: res.deprecated_for,
+            superseded_by: res.superseded_by.map(|module| module.slug),
+            releases,
+            endorsement: res.endorsement,
+        })
+    }
+}
+
+/// The details of the deprecation in `entry`, `None` if the module is not deprecated
+fn deprecation(entry: &CacheEntry) -> Option<Deprecation> {
+    entry.is_deprecated.then(|| Deprecation {
+        at: entry.deprecated_at,
+        reason: entry.deprecated_for.clone(),
+        replacement: entry.superseded_by.clone().or_else(|| {
+            entry
+                .deprecated_for
+                .as_deref()
+                .and_then(replacement_from_reason)
+        }),
+    })
+}
+
+/// Converts a Puppet-style version requirement (`>= 4.13.1 < 10.0.0`, `1.x`, `4.2.0`) to a
+/// `VersionReq`. Unlike in Cargo, a bare version only matches that exact version.
+pub fn parse_version_requirement(requirement: &str) -> Result<VersionReq, semver::Error> {
+    static COMPARATOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?P<op>>=|<=|>|<|=)?\s*(?P<version>[0-9][0-9xX*.]*)").unwrap()
+    });
+    let comparators: Vec<String> = COMPARATOR_RE
+        .captures_iter(requirement)
+        .map(|caps| {
+            let version = &caps["version"];
+            match caps.name("op") {
+                Some(op) => format!("{}{version}", op.as_str()),
+                // wildcards already are ranges
+                None if version.contains(['x', 'X', '*']) => version.to_owned(),
+                None => format!("={version}"),
+            }
+        })
+        .collect();
+    if comparators.is_empty() {
+        // an empty requirement allows any version
+        return VersionReq::parse("*");
+    }
+    VersionReq::parse(&comparators.join(", "))
+}
+
+/// Finds the module named in the reason for a deprecation, like `puppet/network` in "use
+/// puppet/network instead". Only the `author/module` form is recognized, `author-module` can't
+/// be told apart from ordinary hyphenated words.
+fn replacement_from_reason(reason: &str) -> Option<String> {
+    static MODULE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?:^|[\s'"`(])([a-z0-9]+)/([a-z][a-z0-9_]*)(?:$|[\s'"`).,;:!?])"#).unwrap()
+    });
+    let caps = MODULE_RE.captures(reason)?;
+    Some(format!("{}-{}", &caps[1], &caps[2]))
+}
+
+/// Parses the value of a `Retry-After` header, which is either a number of seconds or a date
+/// (relative to `now`)
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    // a date in the past means right away
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// Builds the query leaving out the `LARGE_FIELDS` except for the `needed` ones
+fn exclude_fields(needed: &[&str]) -> String {
+    let excluded: Vec<&str> = LARGE_FIELDS
+        .into_iter()
+        .filter(|field| !needed.contains(field))
+        .collect();
+    format!("exclude_fields={}", excluded.join(","))
+}
+
+/// Returns the host part of `url`, e.g. `forgeapi.puppet.com` for `https://forgeapi.puppet.com/`
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    host.split(':').next().unwrap_or_default()
+}
+
+/// Returns the password of the `machine` entry for `host` in the netrc file `contents`. The
+/// `default` entry is ignored, its password is meant for some other service and must not be sent
+/// to whatever host the Forge is on. Macros (`macdef`) are not supported.
+fn netrc_password(contents: &str, host: &str) -> Option<String> {
+    let mut tokens = contents.split_whitespace();
+    // whether the entry being read is for `host`
+    let mut current = false;
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => current = tokens.next() == Some(host),
+            "default" => current = false,
+            "password" => {
+                let password = tokens.next()?;
+                if current {
+                    return Some(password.to_string());
+                }
+            }
+            "login" | "account" => {
+                tokens.next();
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puppet_version_requirements() {
+        let req = parse_version_requirement(">= 4.13.1 < 10.0.0").unwrap();
+        assert!(req.matches(&Version::new(9, 7, 0)));
+        assert!(!req.matches(&Version::new(10, 0, 0)));
+        assert!(!req.matches(&Version::new(4, 13, 0)));
+
+        let req = parse_version_requirement("1.x").unwrap();
+        assert!(req.matches(&Version::new(1, 9, 0)));
+        assert!(!req.matches(&Version::new(2, 0, 0)));
+
+        let req = parse_version_requirement("4.2.0").unwrap();
+        assert!(req.matches(&Version::new(4, 2, 0)));
+        assert!(!req.matches(&Version::new(4, 2, 1)));
+
+        assert!(parse_version_requirement("")
+            .unwrap()
+            .matches(&Version::new(0, 1, 0)));
+    }
+
+    #[test]
+    fn entry() {
+        let mut api = ForgeApi::new(None, "https://forge.example.com", None);
+        api.use_snapshot();
+        api.cache.lock().unwrap().insert(
+            "author-module".to_string(),
+            CacheEntry {
+                version: "2.0.0-rc1".to_string(),
+                is_deprecated: true,
+                time_fetched: 0,
+                releases: ["2.0.0-rc1", "1.4.0", "1.3.2"]
+                    .into_iter()
+                    .map(|version| ReleaseEntry {
+                        version: version.to_string(),
+                        created_at: None,
+                    })
+                    .collect(),
+                endorsement: None,
+                not_found: false,
+                deprecated_at: None,
+                deprecated_for: Some("Use other/module instead".to_string()),
+                superseded_by: None,
+                forge: "https://forge.example.com".to_string(),
+            },
+        );
+        let entry = api.get_entry("author-module").unwrap();
+        assert_eq!(entry.version, Some(Version::new(1, 4, 0)));
+        assert_eq!(
+            entry.deprecation.and_then(|d| d.replacement).as_deref(),
+            Some("other-module")
+        );
+        assert_eq!(
+            api.get_version("author-module").unwrap(),
+            Version::new(1, 4, 0)
+        );
+        assert!(matches!(
+            api.get_entry("author-other"),
+            Err(ForgeError::NotFound(_))
+        ));
+
+        // a broken version in the cache counts as no release
+        api.cache
+            .lock()
+            .unwrap()
+            .get_mut("author-module")
+            .unwrap()
+            .version = "1.x".to_string();
+        assert!(matches!(
+            api.get_version("author-module"),
+            Err(ForgeError::NoReleases(_))
+        ));
+    }
+
+    #[test]
+    fn known_missing_counted_once() {
+        let api = ForgeApi::new(None, "https://forge.example.com", None);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        api.cache.lock().unwrap().insert(
+            "author-missing".to_string(),
+            CacheEntry {
+                version: String::new(),
+                is_deprecated: false,
+                time_fetched: now,
+                releases: vec![],
+                endorsement: None,
+                not_found: true,
+                deprecated_at: None,
+                deprecated_for: None,
+                superseded_by: None,
+                forge: "https://forge.example.com".to_string(),
+            },
+        );
+        for _ in 0..3 {
+            assert!(matches!(
+                api.get_version("author-missing"),
+                Err(ForgeError::NotFound(_))
+            ));
+        }
+        assert_eq!(api.cache_stats(), (1, 0));
+    }
+
+    #[test]
+    fn author_forges() {
+        let config = ForgeConfig::parse(
+            r#"
+[[forge]]
+author = "mycompany"
+url = "https://forge.example.com/"
+token = "secret"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.forge.len(), 1);
+        assert!(ForgeConfig::parse("[[forge]]\nauthor = \"mycompany\"\n").is_err());
+
+        let mut api = ForgeApi::new(None, "https://forgeapi.puppet.com", None);
+        for forge in config.forge {
+            api.add_author_forge(&forge.author, &forge.url, forge.token);
+        }
+        assert_eq!(
+            api.module_url("mycompany/profile"),
+            format!(
+                "https://forge.example.com/v3/modules/mycompany-profile?{}",
+                exclude_fields(&[])
+            )
+        );
+        assert_eq!(
+            api.endpoint("mycompany-profile-1.0.0")
+                .authorization
+                .as_deref(),
+            Some("Bearer secret")
+        );
+        assert!(api
+            .module_url("puppetlabs-stdlib")
+            .starts_with("https://forgeapi.puppet.com/"));
+        // only the author counts, not a module named like it
+        assert!(api
+            .module_url("puppetlabs-mycompany")
+            .starts_with("https://forgeapi.puppet.com/"));
+    }
+
+    #[test]
+    fn response_without_releases() {
+        let res: ForgeResponse =
+            serde_json::from_str(r#"{"current_release": null, "deprecated_at": null}"#).unwrap();
+        assert!(res.current_release.is_none());
+        let res: ForgeResponse = serde_json::from_str(r#"{"releases": []}"#).unwrap();
+        assert!(res.current_release.is_none());
+    }
+
+    #[test]
+    fn cache_entries_per_forge() {
+        // entries cached by older versions came from the public Forge, they are not used for
+        // another one
+        let old_entry = r#"{"version": "1.0.0", "is_deprecated": false, "time_fetched": 0}"#;
+        let mut api = ForgeApi::new(None, DEFAULT_FORGE_URL, None);
+        api.use_snapshot();
+        api.cache.lock().unwrap().insert(
+            "author-module".to_string(),
+            serde_json::from_str(old_entry).unwrap(),
+        );
+        assert!(api.get_version("author-module").is_ok());
+        let mut api = ForgeApi::new(None, "https://forge.example.com", None);
+        api.use_snapshot();
+        api.cache.lock().unwrap().insert(
+            "author-module".to_string(),
+            serde_json::from_str(old_entry).unwrap(),
+        );
+        assert!(matches!(
+            api.get_version("author-module"),
+            Err(ForgeError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn no_releases() {
+        let mut api = ForgeApi::new(None, "https://forge.example.com", None);
+        api.use_snapshot();
+        api.cache.lock().unwrap().insert(
+            "author-empty".to_string(),
+            CacheEntry {
+                version: String::new(),
+                is_deprecated: false,
+                time_fetched: 0,
+                releases: vec![],
+                endorsement: Some("approved".to_string()),
+                not_found: false,
+                deprecated_at: None,
+                deprecated_for: None,
+                superseded_by: None,
+                forge: "https://forge.example.com".to_string(),
+            },
+        );
+        // only the lookups about the latest release fail
+        assert!(matches!(
+            api.get_version("author-empty"),
+            Err(ForgeError::NoReleases(_))
+        ));
+        assert!(matches!(
+            api.get_release_date("author-empty"),
+            Err(ForgeError::NoReleases(_))
+        ));
+        assert_eq!(api.get_entry("author-empty").unwrap().version, None);
+        assert!(!api.is_deprecated("author-empty").unwrap());
+        assert!(api.get_deprecation("author-empty").unwrap().is_none());
+        assert_eq!(
+            api.get_endorsement("author-empty").unwrap().as_deref(),
+            Some("approved")
+        );
+        assert!(api.get_releases("author-empty").unwrap().is_empty());
+    }
+
+    #[test]
+    fn replacement() {
+        let res: ForgeResponse = serde_json::from_str(
+            r#"{"deprecated_for": "moved", "superseded_by": {"uri": "/v3/modules/puppet-network", "slug": "puppet-network"}}"#,
+        )
+        .unwrap();
+        assert_eq!(res.superseded_by.unwrap().slug, "puppet-network");
+
+        assert_eq!(
+            replacement_from_reason("Use puppet/network instead."),
+            Some("puppet-network".to_string())
+        );
+        assert_eq!(
+            replacement_from_reason("Replaced by stdlib-based functions"),
+            None
+        );
+        assert_eq!(replacement_from_reason("see https://example.com/foo"), None);
+    }
+
+    #[test]
+    fn release_metadata() {
+        let res: ForgeReleaseResponse = serde_json::from_str(
+            r#"{"metadata": {
+                "dependencies": [{"name": "puppetlabs/stdlib", "version_requirement": ">= 4.13.1 < 10.0.0"}],
+                "requirements": [{"name": "puppet", "version_requirement": ">= 7.0.0 < 9.0.0"}]
+            }}"#,
+        )
+        .unwrap();
+        let puppet = &res.metadata.requirements[0];
+        assert_eq!(puppet.name, "puppet");
+        let req =
+            parse_version_requirement(puppet.version_requirement.as_deref().unwrap()).unwrap();
+        assert!(req.matches(&Version::new(8, 0, 0)));
+        assert!(!req.matches(&Version::new(9, 0, 0)));
+
+        // asked for with the module
+        let res: ForgeResponse = serde_json::from_str(
+            r#"{"current_release": {"version": "1.0.0", "metadata": {"requirements": []}}}"#,
+        )
+        .unwrap();
+        assert!(res.current_release.unwrap().metadata.is_some());
+
+        // older releases may not declare anything
+        let res: ForgeReleaseResponse = serde_json::from_str(r#"{"metadata": {}}"#).unwrap();
+        assert!(res.metadata.requirements.is_empty());
+        assert!(res.metadata.operatingsystem_support.is_empty());
+    }
+
+    #[test]
+    fn os_support() {
+        let res: ForgeReleaseResponse = serde_json::from_str(
+            r#"{"metadata": {"operatingsystem_support": [
+                {"operatingsystem": "RedHat", "operatingsystemrelease": ["8", "9"]},
+                {"operatingsystem": "Ubuntu", "operatingsystemrelease": ["22.04"]},
+                {"operatingsystem": "Archlinux"}
+            ]}}"#,
+        )
+        .unwrap();
+        let supports = |name: &str, release: Option<&str>| {
+            res.metadata
+                .operatingsystem_support
+                .iter()
+                .any(|os| os.covers(name, release))
+        };
+        assert!(supports("redhat", Some("9")));
+        assert!(supports("RedHat", Some("9.2")));
+        assert!(!supports("RedHat", Some("10")));
+        assert!(!supports("RedHat", Some("90")));
+        assert!(supports("Ubuntu", Some("22.04")));
+        assert!(!supports("Ubuntu", Some("24.04")));
+        assert!(supports("Ubuntu", None));
+        assert!(supports("Archlinux", Some("2024")));
+        assert!(!supports("Debian", None));
+    }
+
+    #[test]
+    fn excluded_fields() {
+        assert_eq!(
+            exclude_fields(&[]),
+            "exclude_fields=readme,changelog,license,reference,tasks,plans,metadata,tags"
+        );
+        assert_eq!(
+            exclude_fields(&["metadata"]),
+            "exclude_fields=readme,changelog,license,reference,tasks,plans,tags"
+        );
+    }
+
+    #[test]
+    fn retry_after() {
+        let now = DateTime::parse_from_rfc3339("2024-10-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Tue, 01 Oct 2024 12:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Tue, 01 Oct 2024 11:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn netrc() {
+        assert_eq!(
+            url_host("https://forgeapi.puppet.com"),
+            "forgeapi.puppet.com"
+        );
+        assert_eq!(
+            url_host("http://user@forge.example.com:8080/api"),
+            "forge.example.com"
+        );
+
+        let contents = "machine github.com login me password nope\n\
+            machine forgeapi.puppet.com\n  login me\n  password s3cret\n\
+            default login anonymous password guest\n";
+        assert_eq!(
+            netrc_password(contents, "forgeapi.puppet.com"),
+            Some("s3cret".to_string())
+        );
+        assert_eq!(netrc_password(contents, "forge.example.com"), None);
+        assert_eq!(
+            netrc_password("machine github.com password nope", "forgeapi.puppet.com"),
+            None
+        );
     }
 }
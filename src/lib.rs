@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Library part of `puppetstuff`: reading and parsing the `Puppetfile`s of a repository's branches,
+//! querying the Puppet Forge and comparing the two.
+//!
+//! The `puppetstuff` binary is a thin CLI on top of this.
+
+pub mod forge;
+pub mod models;
+pub mod puppetfile;
+pub mod remote;
+pub mod repo;
+pub mod report;
@@ -1,19 +1,27 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{modifiers::UTF8_ROUND_CORNERS, Cell};
-use comfy_table::{Color, Table};
+use comfy_table::{Color, ContentArrangement, Table};
 use git2::BranchType;
 use semver::Version;
 use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::process;
-use tracing::{debug, error, span, trace, warn};
+use std::sync::LazyLock;
+use std::time::Instant;
+use tracing::{debug, error, span, warn};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 
-mod forge;
-mod models;
-mod puppetfile;
+use puppetstuff::repo::{full_branch_name, short_branch_name, RepoError};
+use puppetstuff::report::{Baseline, Status, Summary, TableCell, VersionCell};
+use puppetstuff::{forge, models, remote, repo, report};
 
+mod tui;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Subcommand)]
 enum View {
     /// Show latest releases for forge-crates
@@ -22,6 +30,55 @@ enum View {
     ForgeBranches,
     /// Show deprecated modules
     ForgeDeprecated,
+    /// Show modules used by only one branch, candidates for removal
+    Orphans,
+    /// Show branches using exactly the same modules, candidates for consolidation
+    Identical,
+    /// Show the git modules of the branches, with their reference and install path
+    GitModules,
+    /// List the names of the Forge modules in use, one per line and without decoration
+    Modules,
+    /// Show dependencies that the Forge modules of a branch disagree on
+    Conflicts {
+        /// Branch to check, without the remote's name
+        branch: String,
+    },
+    /// Print the dependencies between the Forge modules of a branch as a Graphviz (DOT) graph,
+    /// e.g. for `dot -Tsvg`
+    Graph {
+        /// Branch to draw, without the remote's name
+        branch: String,
+    },
+    /// Print the Puppetfile of a branch with the Forge modules updated to their latest release
+    Bump {
+        /// Branch to update, without the remote's name
+        branch: String,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the modules whose version or deprecation changed between two cache files
+    Drift {
+        /// The older cache file
+        old_cache: std::path::PathBuf,
+        /// The newer cache file
+        new_cache: std::path::PathBuf,
+    },
+    /// Show all releases of a module on the Forge, newest first
+    ForgeReleases {
+        /// Name of the module, e.g. puppetlabs-stdlib or puppetlabs/stdlib
+        module: String,
+        /// Add the date of each release
+        #[arg(long)]
+        with_dates: bool,
+    },
+    /// Check that the repository, its branches, the Forge and the cache file can be used
+    Doctor,
+    /// Print the completion script for a shell, e.g. `puppetstuff completions bash`
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -30,437 +87,3024 @@ enum OutputFormat {
     Jira,
     /// MarkDown
     Md,
+    /// HTML table, styled via CSS classes
+    Html,
+    /// AsciiDoc table
+    Adoc,
     /// Pretty for the terminal
     TerminalTable,
+    /// Tab-separated values without quoting or markers, for spreadsheets and `cut`/`awk`
+    Tsv,
+    /// One JSON object per row, for log processors and `jq`
+    Ndjson,
+}
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Jira => write!(f, "jira"),
+            OutputFormat::Md => write!(f, "markdown"),
+            OutputFormat::Html => write!(f, "html"),
+            OutputFormat::Adoc => write!(f, "adoc"),
+            OutputFormat::TerminalTable => write!(f, "terminal-table"),
+            OutputFormat::Tsv => write!(f, "tsv"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum MdStyle {
+    /// Just the table
+    Plain,
+    /// The table in a collapsible section, with a line of counts shown while it is collapsed
+    Github,
+}
+impl std::fmt::Display for MdStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MdStyle::Plain => write!(f, "plain"),
+            MdStyle::Github => write!(f, "github"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum NameStyle {
+    /// `author/module`, like on the Forge's website
+    Slash,
+    /// `author-module`, like the modules' directories and the Forge-API
+    Dash,
+}
+impl std::fmt::Display for NameStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameStyle::Slash => write!(f, "slash"),
+            NameStyle::Dash => write!(f, "dash"),
+        }
+    }
+}
+impl NameStyle {
+    /// Formats the name of a Forge module, which is kept as `author-module` internally
+    fn display(self, name: &str) -> String {
+        match self {
+            NameStyle::Slash => name.replacen('-', "/", 1),
+            NameStyle::Dash => name.to_string(),
+        }
+    }
+}
+
+/// How the tables show the modules: named in `style` and linked to their page on the Forge's
+/// website, if `api` knows one
+struct ModuleNames<'a> {
+    style: NameStyle,
+    api: &'a forge::ForgeApi,
+}
+
+impl ModuleNames<'_> {
+    fn display(&self, name: &str) -> String {
+        self.style.display(name)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SortOrder {
+    /// Alphabetically by module name
+    Name,
+    /// Most outdated modules first, by the gap between the Forge and the oldest version in use
+    Lag,
+    /// Deprecated modules first
+    Deprecated,
+}
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortOrder::Name => write!(f, "name"),
+            SortOrder::Lag => write!(f, "lag"),
+            SortOrder::Deprecated => write!(f, "deprecated"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum BranchOrder {
+    /// Alphabetically by branch name
+    Name,
+    /// By the date of the branch's tip commit, oldest first
+    Date,
+    /// By the date of the branch's tip commit, newest first
+    DateDesc,
+}
+impl std::fmt::Display for BranchOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BranchOrder::Name => write!(f, "name"),
+            BranchOrder::Date => write!(f, "date"),
+            BranchOrder::DateDesc => write!(f, "date-desc"),
+        }
+    }
+}
+
+/// `--baseline`, see [`Baseline`]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum BaselineArg {
+    /// The latest release on the Forge, to plan upgrades
+    Forge,
+    /// The newest version used by any branch, to detect drift between branches
+    MaxInUse,
+}
+impl std::fmt::Display for BaselineArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineArg::Forge => write!(f, "forge"),
+            BaselineArg::MaxInUse => write!(f, "max-in-use"),
+        }
+    }
+}
+impl From<BaselineArg> for Baseline {
+    fn from(baseline: BaselineArg) -> Self {
+        match baseline {
+            BaselineArg::Forge => Baseline::Forge,
+            BaselineArg::MaxInUse => Baseline::MaxInUse,
+        }
+    }
+}
+
+/// The statuses as named by `--status-color`, see [`Status`]
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum StatusArg {
+    /// Deprecated on the Forge
+    Deprecated,
+    /// Older than the minimum set with `--min-version`
+    BelowMinimum,
+    /// No release within `--stale-after` days
+    Stale,
+    /// Older than the baseline
+    Behind,
+    /// Newer than the baseline
+    Ahead,
+    /// A git module pinned differently than in most branches
+    Differs,
+    /// Same as the baseline
+    Current,
+}
+impl From<StatusArg> for Status {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Deprecated => Status::Deprecated,
+            StatusArg::BelowMinimum => Status::BelowMinimum,
+            StatusArg::Stale => Status::Stale,
+            StatusArg::Behind => Status::Behind,
+            StatusArg::Ahead => Status::Ahead,
+            StatusArg::Differs => Status::Differs,
+            StatusArg::Current => Status::Current,
+        }
+    }
+}
+
+/// Colours used for the statuses of the versions in the terminal
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ColorScheme {
+    /// Red for deprecated, yellow for behind, green for current
+    Default,
+    /// Colours that can be told apart with the common kinds of colour blindness
+    Colorblind,
 }
-impl ToString for OutputFormat {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            OutputFormat::Jira => "jira".into(),
-            OutputFormat::Md => "markdown".into(),
-            OutputFormat::TerminalTable => "terminal-table".into(),
+            ColorScheme::Default => write!(f, "default"),
+            ColorScheme::Colorblind => write!(f, "colorblind"),
         }
     }
 }
+impl ColorScheme {
+    fn color(self, status: Status) -> Color {
+        match self {
+            ColorScheme::Default => match status {
+                Status::Deprecated => Color::Red,
+                Status::BelowMinimum => Color::Magenta,
+                Status::Stale => Color::Grey,
+                Status::Behind => Color::DarkYellow,
+                Status::Ahead => Color::Yellow,
+                Status::Differs => Color::Cyan,
+                Status::Current => Color::DarkGreen,
+            },
+            // the Okabe-Ito palette
+            ColorScheme::Colorblind => match status {
+                Status::Deprecated => Color::Rgb {
+                    r: 213,
+                    g: 94,
+                    b: 0,
+                },
+                Status::BelowMinimum => Color::Rgb {
+                    r: 204,
+                    g: 121,
+                    b: 167,
+                },
+                Status::Stale => Color::Grey,
+                Status::Behind => Color::Rgb {
+                    r: 230,
+                    g: 159,
+                    b: 0,
+                },
+                Status::Ahead => Color::Rgb {
+                    r: 240,
+                    g: 228,
+                    b: 66,
+                },
+                Status::Differs => Color::Rgb {
+                    r: 0,
+                    g: 158,
+                    b: 115,
+                },
+                Status::Current => Color::Rgb {
+                    r: 86,
+                    g: 180,
+                    b: 233,
+                },
+            },
+        }
+    }
+}
+
+/// Background colours of the statuses in the terminal: the ones of `--color-scheme`, overridden by
+/// `--status-color`. A status without colour is shown as plain text
+struct Palette(HashMap<Status, Option<Color>>);
+
+impl Palette {
+    fn new(scheme: ColorScheme, overrides: &[(Status, Option<Color>)]) -> Self {
+        let mut colors: HashMap<Status, Option<Color>> = Status::ALL
+            .into_iter()
+            .map(|status| (status, Some(scheme.color(status))))
+            .collect();
+        colors.extend(overrides.iter().copied());
+        Palette(colors)
+    }
+
+    fn color(&self, status: Status) -> Option<Color> {
+        self.0.get(&status).copied().flatten()
+    }
+}
+
+/// How the formats without colours mark the statuses
+trait StatusMarkers {
+    fn jira_marker(self) -> &'static str;
+    fn md_marker(self, md_style: MdStyle) -> &'static str;
+    fn adoc_marker(self) -> &'static str;
+    fn css_class(self) -> &'static str;
+}
+
+impl StatusMarkers for Status {
+    fn jira_marker(self) -> &'static str {
+        match self {
+            Status::Deprecated => " (x)",
+            Status::BelowMinimum => " (-)",
+            Status::Stale => " (off)",
+            Status::Behind => " (!)",
+            Status::Ahead => " (?)",
+            Status::Differs => " (i)",
+            Status::Current => "",
+        }
+    }
+
+    /// The GitHub style marks every version, like a task list
+    fn md_marker(self, md_style: MdStyle) -> &'static str {
+        match self {
+            Status::Deprecated => " 🔥",
+            Status::BelowMinimum => " ⛔",
+            Status::Stale => " 💤",
+            Status::Behind => " ⏰",
+            Status::Ahead => " ❓",
+            Status::Differs => " 🔀",
+            Status::Current if md_style == MdStyle::Github => " ✅",
+            Status::Current => "",
+        }
+    }
+
+    fn adoc_marker(self) -> &'static str {
+        match self {
+            Status::Deprecated => " (deprecated)",
+            Status::BelowMinimum => " (below minimum)",
+            Status::Stale => " (stale)",
+            Status::Behind => " (behind)",
+            Status::Ahead => " (ahead)",
+            Status::Differs => " (differs)",
+            Status::Current => "",
+        }
+    }
+
+    fn css_class(self) -> &'static str {
+        match self {
+            Status::Deprecated => "deprecated",
+            Status::BelowMinimum => "below-minimum",
+            Status::Stale => "stale",
+            Status::Behind => "outdated",
+            Status::Ahead => "ahead",
+            Status::Differs => "differs",
+            Status::Current => "current",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum FailOn {
+    /// A branch uses an older version than the latest on the Forge (see `--max-behind`)
+    Outdated,
+    /// A branch uses a module that is deprecated on the Forge
+    Deprecated,
+    /// A branch uses an older version than required by `--min-version`
+    BelowMinimum,
+    /// The latest release of a module does not claim to support an operating system of `--os`
+    /// (latest view)
+    UnsupportedOs,
+}
 
 #[derive(Parser)]
 struct Cli {
-    /// Clone to work on, omit for current directory
+    /// Clone to work on, omit for current directory. A remote URL is cloned to a temporary
+    /// directory. Can be given multiple times to combine several repositories in the latest view
+    #[arg(short, long, env = "PUPPETSTUFF_REPO")]
+    repo: Vec<String>,
+    /// Path of the Puppetfile within the repository, `{branch}` is replaced by the branch's name
+    #[arg(long, default_value = "Puppetfile")]
+    puppetfile: String,
+    /// Remote whose branches are looked at
+    #[arg(long, env = "PUPPETSTUFF_REMOTE", default_value = "origin")]
+    remote: String,
+    /// Add the branches of all other remotes as if they were branches of `--remote`, which wins
+    /// if several remotes have a branch of the same name
+    #[arg(long)]
+    merge_remotes: bool,
+    /// Only list the Forge modules that would be looked up and the URLs requested for them, then
+    /// exit without contacting the Forge
+    #[arg(long)]
+    plan: bool,
+    /// Look up at most this many modules on the Forge at the same time, 1 looks them up one after
+    /// the other
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+    /// Exit on the first module that can't be looked up on the Forge, e.g. because it could not
+    /// be reached
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+    /// Show the modules that can't be looked up on the Forge as failed and list them at the end
+    /// (the default)
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Fetch the remote before looking at its branches
+    #[arg(long)]
+    fetch: bool,
+    /// SSH key to try for fetching if neither the ssh-agent nor the default keys work
+    #[arg(long)]
+    ssh_key: Option<String>,
+    /// Format of the diagnostics written to stderr
+    #[arg(long, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Write more diagnostics to stderr, `-vv` for even more. `RUST_LOG` takes precedence if set
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Write only errors to stderr, no warnings
+    #[arg(short, long)]
+    quiet: bool,
+    /// Write the results to this file instead of stdout
     #[arg(short, long)]
-    repo: Option<String>,
+    output: Option<String>,
     /// Output format
-    #[arg(short, long, default_value_t = OutputFormat::TerminalTable)]
+    #[arg(short, long, env = "PUPPETSTUFF_FORMAT", default_value_t = OutputFormat::TerminalTable)]
     format: OutputFormat,
-    /// Show only this branch in views that support it
+
+    /// Flavour of the Markdown output of the views showing versions
+    #[arg(long, default_value_t = MdStyle::Plain)]
+    md_style: MdStyle,
+    /// How the names of Forge modules are shown in tables and links
+    #[arg(long, default_value_t = NameStyle::Slash)]
+    name_style: NameStyle,
+    /// Show only this branch in views that support it, or all branches matching a glob like
+    /// `release/*`. Can be given multiple times
+    #[arg(short, long)]
+    branch: Vec<String>,
+    /// Add the (possibly uncommitted) Puppetfile in the working tree as a branch named WORKING
+    #[arg(long)]
+    working: bool,
+    /// Look at the Puppetfile of this tag or commit (anything `git rev-parse` understands)
+    /// instead of the branches of the remote
+    #[arg(long = "ref", value_name = "REF", conflicts_with_all = ["merge_remotes", "exclude_branch"])]
+    git_ref: Option<String>,
+    /// Show the view again whenever the Puppetfile in the working tree changes, implies
+    /// `--working`
+    #[arg(long)]
+    watch: bool,
+    /// Ignore branches matching this glob (e.g. `feature/*`), can be given multiple times
+    #[arg(long)]
+    exclude_branch: Vec<String>,
+    /// Add a column with the module's endorsement by Puppet in the latest view
+    #[arg(long)]
+    show_endorsement: bool,
+    /// Add a column to the latest view telling whether the latest release of each module supports
+    /// this version of Puppet, e.g. `8` or `7.24`
+    #[arg(long, value_parser = parse_puppet_version)]
+    puppet_version: Option<Version>,
+    /// Add a column to the latest view listing the operating systems given like `RedHat:9` or
+    /// `Ubuntu:22.04` (the release is optional) that the latest release of a module does not
+    /// claim to support. Can be given multiple times
+    #[arg(long, value_parser = parse_os)]
+    os: Vec<(String, Option<String>)>,
+    /// What the branch versions are compared against in the branches view
+    #[arg(long, default_value_t = BaselineArg::Forge)]
+    baseline: BaselineArg,
+    /// Don't explain the colours and markers below the table
+    #[arg(long)]
+    no_legend: bool,
+    /// Colours of the versions in the terminal. The markers of the other formats differ in shape
+    /// and don't depend on it
+    #[arg(long, default_value_t = ColorScheme::Default)]
+    color_scheme: ColorScheme,
+    /// Colour of a status, overriding the one of `--color-scheme`, e.g. `deprecated=dark-red`,
+    /// `current=#56b4e9` or `current=none`. Can be given multiple times
+    #[arg(long, value_parser = parse_status_color)]
+    status_color: Vec<(Status, Option<Color>)>,
+    /// Print how long reading the branches and looking up the modules took to stderr, along with
+    /// the use of the Forge cache
+    #[arg(long)]
+    timings: bool,
+    /// Show the branches as rows and the modules as columns in the branches view
+    #[arg(long)]
+    transpose: bool,
+    /// Split the branches view into several tables of this many branches each, repeating the
+    /// module names and the Forge's versions in each of them. Not for TSV and NDJSON, which are
+    /// meant for programs
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), conflicts_with_all = ["transpose", "tui"])]
+    page_branches: Option<u16>,
+    /// Add the latest version on the Forge to the outdated versions in the branches view, like
+    /// `8.5.0 (→9.1.0)`, for tables that lose their colours
+    #[arg(long)]
+    show_target: bool,
+    /// Browse the branches view in an interactive table instead of printing it
+    #[arg(long)]
+    tui: bool,
+    /// Columns of the branches view to show, in this order (comma-separated), from `name`,
+    /// `forge` and the names of the branches
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+    /// Add the git modules pinned to semver tags to the branches view, compared with the newest
+    /// tag in use
+    #[arg(long)]
+    git_tags: bool,
+    /// Add all git modules to the branches view, showing their reference in each branch and
+    /// marking the ones pinned differently than in most branches
+    #[arg(long)]
+    include_git: bool,
+    /// Add a row counting the current and outdated (including deprecated and stale) modules of each
+    /// branch in the branches view
+    #[arg(long)]
+    totals: bool,
+    /// Write key figures of the branches view as a single JSON line to this file, regardless of
+    /// `--format`
+    #[arg(long)]
+    summary_file: Option<String>,
+    /// Print the tip commit of each branch below the table in views that support it
+    #[arg(long)]
+    show_commit_info: bool,
+    /// Wrap the terminal tables of the branches and latest views to this many columns, defaults
+    /// to the width of the terminal
+    #[arg(long)]
+    max_width: Option<u16>,
+    /// Order of the branch columns in views that support it
+    #[arg(long, default_value_t = BranchOrder::Name)]
+    order_branches: BranchOrder,
+    /// Order of the modules in views that support it
+    #[arg(long, default_value_t = SortOrder::Name)]
+    sort: SortOrder,
+    /// Show at most this many modules in views that support it, or groups of branches in the
+    /// identical view
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Compare against the Forge as recorded in this cache file, without asking the Forge, e.g.
+    /// a copy of the cache from last week
+    #[arg(long)]
+    snapshot: Option<String>,
+    /// Only show the modules in use whose latest version or deprecation changed since this cache
+    /// file was written, or that came into or went out of use since, in the latest view
+    #[arg(long)]
+    changes_since: Option<String>,
+    /// File the answers of the Forge are cached in between runs
+    #[arg(long, env = "PUPPETSTUFF_CACHE_FILE", default_value = "/tmp/asdf.json")]
+    cache_file: String,
+    /// URL of the Forge-API to ask, e.g. for an internal mirror
+    #[arg(
+        long,
+        env = "PUPPETSTUFF_FORGE_URL",
+        default_value = forge::DEFAULT_FORGE_URL
+    )]
+    forge_url: String,
+    /// API token sent to the Forge. If unset, the password of the Forge's host in `~/.netrc` is
+    /// used if there is one
+    #[arg(long, env = "FORGE_API_TOKEN", hide_env_values = true)]
+    forge_token: Option<String>,
+    /// TOML file naming other Forges for the modules of some authors, like
+    /// `[[forge]] author = "mycompany"  url = "https://…"`
+    #[arg(long, env = "PUPPETSTUFF_FORGE_CONFIG")]
+    forge_config: Option<String>,
+    /// Consider pre-releases as the latest version of a module
+    #[arg(long)]
+    include_prereleases: bool,
+    /// Exit with code 2 if a shown branch violates one of these policies (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    fail_on: Vec<FailOn>,
+    /// Only show modules whose latest release is newer than this, e.g. `2024-10-01` or `30d`
+    #[arg(long, value_parser = parse_since)]
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// With `--fail-on outdated`, only fail if a module is more than this far behind the Forge,
+    /// e.g. `minor:2` or `major:1`
+    #[arg(long, value_parser = parse_max_behind)]
+    max_behind: Option<models::VersionLag>,
+    /// Mark modules whose latest release is older than this many days as stale
+    #[arg(long)]
+    stale_after: Option<u32>,
+    /// Minimum version of a module that branches must use, e.g. `puppetlabs-stdlib=8.0.0`. Can be
+    /// given multiple times
+    #[arg(long, value_parser = parse_min_version)]
+    min_version: Vec<(String, Version)>,
+    /// Only consider modules whose name matches this glob (`puppetlabs-*`), or this regex if
+    /// enclosed in slashes (`/^puppet(labs)?-/`)
     #[arg(short, long)]
-    branch: Option<String>,
+    module: Option<String>,
     #[command(subcommand)]
     view: View,
 }
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    let started = Instant::now();
     let args = Cli::parse();
 
-    let repo_path = match args.repo {
-        Some(p) => shellexpand::tilde(&p).into_owned(),
-        None => String::from("."),
+    // needs neither a repository nor the Forge
+    if let View::Completions { shell } = args.view {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        return;
+    }
+
+    // diagnostics go to stderr, stdout is reserved for the results
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    // the directives in `RUST_LOG` still win, e.g. to trace only the Forge lookups
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_env_filter(filter);
+    match args.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
     };
 
-    let mut api = forge::ForgeApi::new(Some("/tmp/asdf.json".to_string()));
+    if let View::Doctor = args.view {
+        // the checks are about contacting the Forge
+        if args.plan {
+            eprintln!("Sorry, --plan can't be combined with the doctor view.");
+            process::exit(1);
+        }
+        let passed = doctor(&args);
+        process::exit(if passed { 0 } else { 1 });
+    }
 
-    let (branch_modules, forge_names) = parse_git_repo(&repo_path);
+    if args.watch {
+        if args.tui {
+            eprintln!("Sorry, --watch can't be combined with --tui.");
+            process::exit(1);
+        }
+        match args.repo.as_slice() {
+            [] => watch(".", &args.puppetfile, args.working),
+            [repo] if !is_remote_url(repo) => {
+                watch(&shellexpand::tilde(repo), &args.puppetfile, args.working)
+            }
+            _ => {
+                eprintln!("Sorry, --watch needs a single local repository.");
+                process::exit(1);
+            }
+        }
+    }
 
-    // list of all module names we discovered. Some may not be present in a particular branch. Sort
-    // them for consistency.
-    let forge_names = {
-        let mut fname: Vec<String> = forge_names.into_iter().collect();
-        fname.sort();
-        fname
+    let mut out: Box<dyn Write> = match args.output {
+        Some(ref path) => match File::create(shellexpand::tilde(path).as_ref()) {
+            Ok(f) => Box::new(BufWriter::new(f)),
+            Err(e) => {
+                error!("Error creating output file {path}: {e}");
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout()),
     };
 
-    let branch_names = {
-        let mut bname: Vec<String> = branch_modules
-            .clone()
-            .into_iter()
-            .map(|bm| bm.name.clone())
-            .collect();
-        // required for consistent output
-        bname.sort();
-        bname
-    };
-    // format it once for easier querying later
-    let selected_branch = args
-        .branch
-        .and_then(|bname| Some(format!("origin/{bname}")));
-    // if the user wanted a specific branch and we can't find it, we exit with a helpful message
-    if let Some(ref selected_branch) = selected_branch {
-        if !branch_names.contains(&selected_branch) {
-            eprintln!("Sorry, the selected branch is not known. Branches to choose from:");
-            for branch in branch_names {
-                eprintln!("\t{}", branch.replace("origin/", ""));
-            }
+    let ssh_key = args
+        .ssh_key
+        .as_ref()
+        .map(|key| std::path::PathBuf::from(shellexpand::tilde(key).into_owned()));
+
+    if args.repo.len() > 1
+        && !matches!(
+            args.view,
+            View::ForgeLatest | View::ForgeReleases { .. } | View::Drift { .. }
+        )
+    {
+        eprintln!("Sorry, only the forge-latest view supports several repositories.");
+        process::exit(1);
+    }
+    if args.tui && !matches!(args.view, View::ForgeBranches) {
+        eprintln!("Sorry, only the forge-branches view supports --tui.");
+        process::exit(1);
+    }
+    // pages would be told apart by headings, which would be taken for rows
+    if args.page_branches.is_some()
+        && matches!(args.format, OutputFormat::Tsv | OutputFormat::Ndjson)
+    {
+        eprintln!("Sorry, --page-branches can't be combined with --format tsv or ndjson.");
+        process::exit(1);
+    }
+    // the objects of `--format ndjson` are about Forge modules, one per module
+    if args.format == OutputFormat::Ndjson
+        && matches!(args.view, View::ForgeBranches)
+        && (args.transpose || args.tui || args.git_tags || args.include_git)
+    {
+        eprintln!(
+            "Sorry, --format ndjson can't be combined with --transpose, --tui, --git-tags or --include-git."
+        );
+        process::exit(1);
+    }
+    if let Some(ref old_cache) = args.changes_since {
+        if !matches!(args.view, View::ForgeLatest) {
+            eprintln!("Sorry, only the forge-latest view supports --changes-since.");
+            process::exit(1);
+        }
+        if !std::path::Path::new(shellexpand::tilde(old_cache).as_ref()).is_file() {
+            error!("Cache file {old_cache} does not exist");
             process::exit(1);
         }
     }
-
-    match args.view {
-        View::ForgeLatest => {
-            match args.format {
-                OutputFormat::TerminalTable => {
-                    let mut module_overview_table = Table::new();
-                    module_overview_table
-                        .load_preset(UTF8_FULL)
-                        .apply_modifier(UTF8_ROUND_CORNERS)
-                        .set_header(vec!["Name", "Latest"]);
-                    for name in forge_names.into_iter() {
-                        let title = Cell::new(forge_module_console_hyperlink(
-                            &name.replacen("-", "/", 1),
-                            &name,
-                        ))
-                        .add_attribute(comfy_table::Attribute::Underlined);
-
-                        let vers = Cell::new(api.get_version(&name).unwrap().to_string());
-                        let vers = match api.is_deprecated(&name).unwrap() {
-                            true => vers.bg(Color::Red),
-                            false => vers,
-                        };
-
-                        module_overview_table.add_row([title, vers]);
-                    }
-                    println!("{module_overview_table}");
+    // a remote repository is cloned to a temporary directory, which is removed once the branches
+    // have been parsed
+    let mut clone_dirs = vec![];
+    let repo_args = match args.repo.is_empty() {
+        true => vec![String::from(".")],
+        false => args.repo.clone(),
+    };
+    // (as given by the user, local path, whether it was cloned)
+    let mut repos: Vec<(String, String, bool)> = vec![];
+    for repo in repo_args {
+        if is_remote_url(&repo) {
+            let dir = match tempfile::tempdir() {
+                Ok(d) => d,
+                Err(e) => {
+                    error!("Error creating temporary directory: {e}");
+                    process::exit(1);
                 }
-                _ => (),
             };
+            if let Err(e) = remote::clone(&repo, dir.path(), ssh_key.as_deref()) {
+                error!("Error cloning {repo}: {e}");
+                process::exit(1);
+            }
+            let path = dir.path().to_string_lossy().into_owned();
+            clone_dirs.push(dir);
+            repos.push((repo, path, true));
+        } else {
+            let path = shellexpand::tilde(&repo).into_owned();
+            repos.push((repo, path, false));
         }
-        View::ForgeBranches => {
-            #[derive(Debug)]
-            struct ModuleRow {
-                name: String,
-                forge_version: Version,
-                forge_deprecated: bool,
-                max_in_use_version: Version,
-                branch_versions: HashMap<String, Option<Version>>,
-            }
-            let mut module_rows: Vec<ModuleRow> = vec![];
-
-            // find info about a specific module (one at a time) to figure out if and with what
-            // version it is used in a specific branch, or mark it as not used for that branch
-            for mod_name in forge_names {
-                let sp = span!(tracing::Level::DEBUG, "forge-mod-loop", mod_name = mod_name);
-                let _sp = sp.enter();
-                let mut row = ModuleRow {
-                    name: mod_name.clone(),
-                    forge_version: api.get_version(&mod_name).unwrap(),
-                    forge_deprecated: api.is_deprecated(&mod_name).unwrap(),
-                    max_in_use_version: Version::new(0, 0, 0),
-                    branch_versions: HashMap::new(),
-                };
+    }
 
-                for branch in &branch_modules {
-                    debug!("Branch {}", branch.name);
-                    for branch_module in branch.modules.clone() {
-                        // debug!("Branch module: {branch_module:?}");
-                        match branch_module {
-                            models::Module::Forge(name, version) => {
-                                if name == mod_name {
-                                    row.max_in_use_version = std::cmp::max(
-                                        version.clone(),
-                                        row.max_in_use_version.clone(),
-                                    );
-                                    row.branch_versions
-                                        .insert(branch.name.clone(), Some(version.clone()));
-                                    break;
-                                }
-                            }
-                            _ => (),
-                        };
-                    }
-                }
-                module_rows.push(row);
+    let mut api = match args.snapshot {
+        Some(ref snapshot) => {
+            let snapshot = shellexpand::tilde(snapshot).into_owned();
+            if !std::path::Path::new(&snapshot).is_file() {
+                error!("Snapshot {snapshot} does not exist");
+                process::exit(1);
             }
+            let mut api =
+                forge::ForgeApi::new(Some(snapshot), &args.forge_url, args.forge_token.clone());
+            api.use_snapshot();
+            api
+        }
+        None => forge::ForgeApi::new(
+            Some(shellexpand::tilde(&args.cache_file).into_owned()),
+            &args.forge_url,
+            args.forge_token.clone(),
+        ),
+    };
+    for author_forge in load_forge_config(&args).forge {
+        api.add_author_forge(&author_forge.author, &author_forge.url, author_forge.token);
+    }
+    api.include_prereleases(args.include_prereleases);
+    // the latest release's metadata comes along with the module instead of being asked for
+    // separately
+    if matches!(args.view, View::ForgeLatest)
+        && (args.puppet_version.is_some() || !args.os.is_empty())
+    {
+        api.fetch_fields(&["metadata"]);
+    }
 
-            let mut table = Table::new(); // terminal-table
-            let mut fmt_rows: Vec<String> = vec![]; // jira+md
+    // the only view that does not look at the repository
+    if let View::ForgeReleases {
+        ref module,
+        with_dates,
+    } = args.view
+    {
+        let name = module.replacen('/', "-", 1);
+        if args.plan {
+            print_plan(&mut out, &args, &api, &[name]).unwrap();
+            out.flush().unwrap();
+            return;
+        }
+        let releases = match api.get_releases(&name) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Error getting the releases of {name}: {e}");
+                process::exit(1);
+            }
+        };
+        let total = releases.len();
+        let mut rows = report::release_rows(releases, with_dates);
+        if let Some(limit) = args.limit {
+            rows.truncate(limit);
+        }
+        let header: &[&str] = if with_dates {
+            &["Version", "Released"]
+        } else {
+            &["Version"]
+        };
+        print_plain_table(&mut out, args.format, "releases", header, &rows).unwrap();
+        print_limit_footer(&mut out, args.format, args.limit, total, "releases").unwrap();
+        out.flush().unwrap();
+        if args.snapshot.is_none() {
+            store_cache(&api, &shellexpand::tilde(&args.cache_file));
+        }
+        return;
+    }
 
-            let mut table_header: Vec<String> = vec!["Module-Name".into(), "Forge latest".into()];
-            table_header.extend(match selected_branch {
-                Some(ref bname) => vec![bname.clone().replace("origin/", "")],
-                None => branch_names
-                    .clone()
-                    .into_iter()
-                    .map(|bn| bn.replace("origin/", ""))
-                    .collect::<Vec<String>>(),
-            });
+    // compares two snapshots of the cache, no repository needed either
+    if let View::Drift {
+        ref old_cache,
+        ref new_cache,
+    } = args.view
+    {
+        for path in [old_cache, new_cache] {
+            if !path.is_file() {
+                error!("Cache file {} does not exist", path.display());
+                process::exit(1);
+            }
+        }
+        let old = forge::ForgeApi::load_cache(old_cache);
+        let new = forge::ForgeApi::load_cache(new_cache);
+        let rows = report::drift_rows(&old, &new);
+        print_changes(&mut out, &args, &api, &rows).unwrap();
+        out.flush().unwrap();
+        return;
+    }
 
-            // build the table's header
-            match args.format {
-                OutputFormat::TerminalTable => {
-                    table
-                        .load_preset(UTF8_FULL)
-                        .apply_modifier(UTF8_ROUND_CORNERS)
-                        .set_header(table_header);
-                }
-                OutputFormat::Jira => {
-                    fmt_rows.push(format!("||{{{{{}}}}}||", table_header.join("}}||{{")));
-                }
-                OutputFormat::Md => {
-                    fmt_rows.push(format!("|{}|", table_header.join("|")));
-                    // create the simplest-possible header with alignment-specification:
-                    fmt_rows.push(format!(
-                        "|{}|",
-                        (0..table_header.len())
-                            .map(|_| ": - ")
-                            .collect::<Vec<&str>>()
-                            .join("|")
-                    ));
-                }
-            };
+    if args.fetch {
+        for (_, repo_path, _) in repos.iter().filter(|(_, _, cloned)| !cloned) {
+            let fetched = repo::open_repo(repo_path)
+                .and_then(|repo| remote::fetch(&repo, &args.remote, ssh_key.as_deref()));
+            if let Err(e) = fetched {
+                error!("Error fetching {} of {repo_path}: {e}", args.remote);
+                process::exit(1);
+            }
+        }
+    }
 
-            for mod_row in module_rows {
-                trace!("{mod_row:?}");
-                let mut cell_row: Vec<Cell> = vec![];
-                let mut fmt_row: Vec<String> = vec![];
-
-                // Format the module-name+link and the forge version with
-                // freshness-indicators:
-                match args.format {
-                    OutputFormat::TerminalTable => {
-                        cell_row.push(
-                            Cell::new(forge_module_console_hyperlink(
-                                &mod_row.name.replacen("-", "/", 1),
-                                &mod_row.name,
-                            ))
-                            .add_attribute(comfy_table::Attribute::Underlined),
-                        );
+    let exclude_branches: Vec<regex::Regex> = args
+        .exclude_branch
+        .iter()
+        .map(|pattern| match glob_regex(pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                eprintln!("Sorry, the branch pattern '{pattern}' is not valid: {e}");
+                process::exit(1);
+            }
+        })
+        .collect();
 
-                        let forge_cell = Cell::new(&mod_row.forge_version);
-                        let forge_cell = if mod_row.forge_deprecated {
-                            forge_cell.bg(Color::Red).fg(Color::Black)
-                        } else if mod_row.forge_version > mod_row.max_in_use_version {
-                            forge_cell.bg(Color::DarkYellow).fg(Color::Black)
-                        } else {
-                            forge_cell
-                        };
-                        cell_row.push(forge_cell);
-                    }
-                    OutputFormat::Jira => {
-                        fmt_row.push(format!(
-                            "[{}|https://forge.puppet.com/modules/{}]",
-                            &mod_row.name,
-                            &mod_row.name.replacen("-", "/", 1)
-                        ));
-                        if mod_row.forge_deprecated {
-                            fmt_row.push(format!("{{{{{}}}}} (x)", mod_row.forge_version));
-                        } else if mod_row.forge_version > mod_row.max_in_use_version {
-                            fmt_row.push(format!("{{{{{}}}}} (!)", mod_row.forge_version));
-                        } else {
-                            fmt_row.push(format!("{{{{{}}}}}", mod_row.forge_version));
-                        }
-                    }
-                    OutputFormat::Md => {
-                        fmt_row.push(format!(
-                            "[{}](https://forge.puppet.com/modules/{})",
-                            &mod_row.name,
-                            &mod_row.name.replacen("-", "/", 1)
-                        ));
-                        if mod_row.forge_deprecated {
-                            fmt_row.push(format!("`{}` 🔥", mod_row.forge_version));
-                        } else if mod_row.forge_version > mod_row.max_in_use_version {
-                            fmt_row.push(format!("`{}` ⏰", mod_row.forge_version));
-                        } else {
-                            fmt_row.push(format!("`{}`", mod_row.forge_version));
-                        }
-                    }
-                };
+    // branches are named like `origin/production`, the prefix is stripped for display. A
+    // reference is named as given, it is not a branch of the remote.
+    let prefix = match args.git_ref {
+        Some(_) => String::new(),
+        None => format!("{}/", args.remote),
+    };
+    let (repo_label, repo_path, cloned) = &repos[0];
+    let parse_cache = parse_cache_file(&args.cache_file);
+    let mut timings = report::Timings::default();
+    let mut read_repo = |repo_path: &str| {
+        let parsed = match args.git_ref {
+            Some(ref git_ref) => repo::parse_git_ref(
+                repo_path,
+                git_ref,
+                &args.puppetfile,
+                &parse_cache,
+                &mut timings,
+            ),
+            None => repo::parse_git_repo(
+                repo_path,
+                &args.remote,
+                &args.puppetfile,
+                &exclude_branches,
+                args.merge_remotes,
+                &parse_cache,
+                &mut timings,
+            ),
+        };
+        parsed.unwrap_or_else(|e| exit_repo_error(e))
+    };
+    let (mut branch_modules, mut forge_names) = read_repo(repo_path);
+    if args.working {
+        if *cloned {
+            eprintln!("Sorry, --working needs a local clone with a working tree.");
+            process::exit(1);
+        }
+        let working = repo::read_working_tree(repo_path, &args.puppetfile)
+            .unwrap_or_else(|e| exit_repo_error(e));
+        forge_names.extend(working.modules.iter().filter_map(|module| match module {
+            models::Module::Forge(name, _) => Some(name.to_owned()),
+            _ => None,
+        }));
+        branch_modules.push(working);
+    }
+    // the Forge modules used by each repository. Only the first one is looked at by the views
+    // showing branches, the others only add their modules.
+    let mut repo_usage = vec![(repo_label.clone(), forge_names.clone())];
+    for (repo_label, repo_path, _) in &repos[1..] {
+        let (_, names) = read_repo(repo_path);
+        forge_names.extend(names.iter().cloned());
+        repo_usage.push((repo_label.clone(), names));
+    }
+    drop(clone_dirs);
 
-                // iterate by branch to be consistent with the headers
-                for branch_name in branch_names.iter() {
-                    if let Some(ref bname) = selected_branch {
-                        if bname != branch_name {
-                            debug!("Selected branch: {bname} → skipping branch {branch_name}");
-                            continue;
-                        }
-                    }
-                    debug!("branch {branch_name}");
-                    let mut found_in_branch = false;
-                    for (mod_branch_name, branch_version) in mod_row.branch_versions.iter() {
-                        if branch_name == mod_branch_name {
-                            if let Some(branch_version) = branch_version {
-                                if branch_version < &mod_row.max_in_use_version {
-                                    match args.format {
-                                        OutputFormat::TerminalTable => {
-                                            cell_row.push(
-                                                Cell::new(branch_version.to_string())
-                                                    .bg(Color::DarkYellow)
-                                                    .fg(Color::Black),
-                                            );
-                                        }
-                                        OutputFormat::Jira => {
-                                            fmt_row.push(format!("{{{{{branch_version}}}}} (!)"));
-                                        }
-                                        OutputFormat::Md => {
-                                            fmt_row.push(format!("`{branch_version}` ⏰"));
-                                        }
-                                    };
-                                } else if branch_version > &mod_row.max_in_use_version {
-                                    match args.format {
-                                        OutputFormat::TerminalTable => {
-                                            cell_row.push(
-                                                Cell::new(branch_version.to_string())
-                                                    .bg(Color::Yellow)
-                                                    .fg(Color::Black),
-                                            );
-                                        }
-                                        OutputFormat::Jira => {
-                                            fmt_row.push(format!("{{{{{branch_version}}}}} (x)"));
-                                        }
-                                        OutputFormat::Md => {
-                                            fmt_row.push(format!("`{branch_version}` 🔥"));
-                                        }
-                                    };
-                                } else {
-                                    match args.format {
-                                        OutputFormat::TerminalTable => {
-                                            cell_row.push(
-                                                Cell::new(branch_version.to_string())
-                                                    .bg(Color::DarkGreen)
-                                                    .fg(Color::Black),
-                                            );
-                                        }
-                                        OutputFormat::Jira => {
-                                            fmt_row.push(format!("{{{{{branch_version}}}}}"));
-                                        }
-                                        OutputFormat::Md => {
-                                            fmt_row.push(format!("`{branch_version}`"));
-                                        }
-                                    };
-                                }
-                            } else {
-                                if args.format == OutputFormat::TerminalTable {
-                                    cell_row.push(Cell::new(""));
-                                } else {
-                                    fmt_row.push(" ".to_string());
-                                }
-                            }
-                            found_in_branch = true;
-                            break;
-                        }
-                    }
-                    if !found_in_branch {
-                        if args.format == OutputFormat::TerminalTable {
-                            cell_row.push(Cell::new(""));
-                        } else {
-                            fmt_row.push(" ".to_string());
-                        }
-                    }
-                }
-                // assemble the row and add it to the table
-                if args.format == OutputFormat::TerminalTable {
-                    table.add_row(cell_row);
-                } else {
-                    fmt_rows.push(format!("|{}|", fmt_row.join("|")));
-                }
+    // list of all module names we discovered. Some may not be present in a particular branch. Sort
+    // them for consistency.
+    let mut forge_names = {
+        let mut fname: Vec<String> = forge_names.into_iter().collect();
+        fname.sort();
+        fname
+    };
+
+    // restrict everything to the modules the user is interested in
+    if let Some(ref pattern) = args.module {
+        let module_re = match module_pattern(pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                eprintln!("Sorry, the module pattern is not valid: {e}");
+                process::exit(1);
             }
-            if args.format == OutputFormat::TerminalTable {
-                println!("{table}");
-            } else {
-                for row in fmt_rows {
-                    println!("{row}");
-                }
+        };
+        let examples: Vec<String> = forge_names.iter().take(5).cloned().collect();
+        forge_names.retain(|name| module_re.is_match(name));
+        if forge_names.is_empty() {
+            eprintln!("Sorry, no module matches '{pattern}'. Modules look like this:");
+            for name in examples {
+                eprintln!("\t{name}");
             }
+            process::exit(1);
         }
-        View::ForgeDeprecated => {}
-    };
+        for branch in branch_modules.iter_mut() {
+            branch.modules.retain(|module| match module {
+                models::Module::Forge(name, _) | models::Module::Git(name, _) => {
+                    module_re.is_match(name)
+                }
+            });
+        }
+    }
 
-    api.store_cache("/tmp/asdf.json");
-}
+    // show what would be looked up, without looking it up
+    if args.plan {
+        print_plan(&mut out, &args, &api, &forge_names).unwrap();
+        out.flush().unwrap();
+        return;
+    }
 
-fn parse_git_repo(repo_path: &str) -> (Vec<models::BranchMeta>, HashSet<String>) {
-    let repo = match git2::Repository::open(repo_path) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("Error opening repo: {e}");
-            std::process::exit(1);
-        }
+    let branch_names: Vec<String> = {
+        let mut branches: Vec<&models::BranchMeta> = branch_modules.iter().collect();
+        // required for consistent output. The other sorts are stable, so branches with the same
+        // date stay sorted by name.
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        match args.order_branches {
+            BranchOrder::Name => (),
+            BranchOrder::Date => branches.sort_by_key(|bm| bm.commit_date),
+            BranchOrder::DateDesc => branches.sort_by_key(|bm| std::cmp::Reverse(bm.commit_date)),
+        };
+        branches.into_iter().map(|bm| bm.name.clone()).collect()
     };
-    let mut branch_modules = vec![];
-    let mut forge_names = HashSet::new();
-
-    let branches = repo.branches(Some(BranchType::Remote)).unwrap();
-    for (branch, _btype) in branches.into_iter().filter_map(|b| b.ok()) {
-        let name = match branch.name() {
-            Ok(n) => match n {
-                Some(n) => n.to_owned(),
-                None => {
-                    warn!("Branch name contains invalid characters, skipping");
-                    continue;
-                }
-            },
+    // each selector is a glob matched against the full name, so `release/*` selects all release
+    // branches. The branches are added in the order of the selectors.
+    let mut selected_branches: Vec<String> = vec![];
+    let mut missing_branches: Vec<&str> = vec![];
+    for selector in &args.branch {
+        let branch_re = match glob_regex(&full_branch_name(selector, &prefix)) {
+            Ok(re) => re,
             Err(e) => {
-                warn!("Could not retrieve branch name: {e}");
-                continue;
+                eprintln!("Sorry, the branch pattern '{selector}' is not valid: {e}");
+                process::exit(1);
             }
         };
-        if !name.starts_with("origin") {
-            debug!("Skipping branch {name}");
-            continue;
-        }
-
-        let reference = branch.into_reference();
-
-        if reference.kind() == Some(git2::ReferenceType::Direct) {
-            if let Some(oid) = reference.target() {
-                debug!("{:?} {oid:?}", reference.name());
-
-                let commit = repo.find_commit(oid).unwrap();
-                // let author_date = commit.author().when();
-                // let commit_date = commit.time();
-                // let author = format!(
-                //     "{} <{}>",
-                //     commit.author().name().unwrap_or("Unknown"),
-                //     commit.author().email().unwrap_or("unknown@unknown")
-                // );
-
-                let tree = commit.tree().unwrap();
-                let pf_entry = match tree.get_name("Puppetfile") {
-                    Some(te) => te,
-                    None => {
-                        warn!("Could not find 'Puppetfile' in the root tree of branch {name}");
-                        continue;
-                    }
-                };
-                let pf_blob = match repo.find_blob(pf_entry.id()) {
-                    Ok(b) => b.content().to_owned(),
-                    Err(e) => {
-                        warn!(
-                            "Could not get blob for tree entry 'Puppetfile' in branch {name}: {e}"
-                        );
-                        continue;
-                    }
-                };
-                let pf_blob = std::str::from_utf8(&pf_blob).unwrap();
+        let matches: Vec<&String> = branch_names
+            .iter()
+            .filter(|bname| branch_re.is_match(bname))
+            .collect();
+        if matches.is_empty() {
+            missing_branches.push(selector);
+        }
+        for bname in matches {
+            if !selected_branches.contains(bname) {
+                selected_branches.push(bname.clone());
+            }
+        }
+    }
+    // if the user wanted specific branches and we can't find them, we exit with a helpful message
+    if !missing_branches.is_empty() {
+        eprintln!(
+            "Sorry, the selected branch(es) {} are not known. Branches to choose from:",
+            missing_branches.join(", ")
+        );
+        for branch in branch_names {
+            eprintln!("\t{}", short_branch_name(&branch, &prefix));
+        }
+        process::exit(1);
+    }
+    // the branches that make up the columns in views that support it, in the requested order
+    let shown_branches = if selected_branches.is_empty() {
+        branch_names.clone()
+    } else {
+        selected_branches
+    };
 
-                let modules = puppetfile::parse_puppetfile(pf_blob);
-                forge_names.extend(modules.iter().filter_map(|module| match module {
-                    models::Module::Forge(name, _) => Some(name.to_owned()),
-                    _ => None,
-                }));
+    // modules that are not on the Forge (e.g. internal ones) can't be compared with it. The ones
+    // that could not be looked up are set aside, the views showing all modules mark them as
+    // failed and they are listed at the end.
+    let mut failed_lookups: Vec<(String, forge::ForgeError)> = vec![];
+    if matches!(
+        args.view,
+        View::ForgeLatest | View::ForgeBranches | View::ForgeDeprecated
+    ) || args.since.is_some()
+    {
+        let _span = span!(tracing::Level::DEBUG, "look-up-modules").entered();
+        let lookups_started = Instant::now();
+        api.prefetch(&forge_names, args.jobs.into());
+        forge_names.retain(|name| match api.get_version(name) {
+            Ok(_) | Err(forge::ForgeError::NoReleases(_)) => true,
+            Err(forge::ForgeError::NotFound(_)) => {
+                warn!("{name} was not found on the Forge, skipping it");
+                false
+            }
+            Err(e) if args.fail_fast => {
+                error!("Error looking up {name}: {e}");
+                process::exit(1);
+            }
+            Err(e) => {
+                warn!("Could not look up {name}, going on without it: {e}");
+                failed_lookups.push((name.clone(), e));
+                false
+            }
+        });
+        timings.add("Looking up modules", lookups_started.elapsed());
+    }
 
-                branch_modules.push(models::BranchMeta {
-                    name,
-                    // oid,
-                    // author_date,
-                    // commit_date,
-                    // author,
-                    modules,
-                });
+    // hide modules that did not publish anything recently
+    if let Some(since) = args.since {
+        forge_names.retain(|name| match api.get_release_date(name) {
+            Ok(Some(released)) => released > since,
+            // a module without releases has not published anything
+            Ok(None) | Err(forge::ForgeError::NoReleases(_)) => {
+                debug!("Release date of {name} is unknown, hiding it");
+                false
             }
-        }
+            Err(e) => {
+                warn!("Could not look up the release date of {name}, going on without it: {e}");
+                failed_lookups.push((name.clone(), e));
+                false
+            }
+        });
     }
-    (branch_modules, forge_names)
-}
 
-fn forge_module_console_hyperlink(href: &str, title: &str) -> String {
-    format!("\x1B]8;;https://forge.puppet.com/modules/{href}\x1B\\{title}\x1B]8;;\x1B\\",)
+    let failed_names: Vec<String> = failed_lookups
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    // modules without a release since then are considered unmaintained
+    let stale_before = args
+        .stale_after
+        .map(|days| chrono::Utc::now() - chrono::Duration::days(days.into()));
+    let baseline = Baseline::from(args.baseline);
+    let palette = Palette::new(args.color_scheme, &args.status_color);
+    let naming = ModuleNames {
+        style: args.name_style,
+        api: &api,
+    };
+    // the statuses that can show up, for the legend
+    let statuses: Vec<Status> = Status::ALL
+        .into_iter()
+        .filter(|status| match status {
+            Status::BelowMinimum => !args.min_version.is_empty(),
+            Status::Stale => args.stale_after.is_some(),
+            Status::Differs => args.include_git,
+            _ => true,
+        })
+        .collect();
+
+    // policy violations found by the views, fail after everything else is done
+    let mut violations: Vec<String> = vec![];
+    // key figures for `--summary-file`, filled by the views that support it
+    let mut summary: Option<Summary> = None;
+    // for the `--changes-since` of a later run, a module that could not be looked up is still in
+    // use
+    let mut in_use: Vec<&String> = forge_names.iter().chain(&failed_names).collect();
+    in_use.sort();
+    in_use.dedup();
+    let in_use = serde_json::to_string(&in_use).unwrap();
+
+    // includes the lookups only some views make, e.g. of the release dates
+    let view_span = span!(tracing::Level::DEBUG, "show-view").entered();
+    let view_started = Instant::now();
+    match args.view {
+        View::ForgeLatest if args.changes_since.is_some() => {
+            let old_cache = shellexpand::tilde(args.changes_since.as_deref().unwrap()).into_owned();
+            // the old cache answers like the Forge did back then. Without the modules in use back
+            // then, only the ones in use now are compared
+            let old_names: HashSet<String> = match std::fs::read_to_string(in_use_file(&old_cache))
+                .map_err(|e| e.to_string())
+                .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+            {
+                Ok(names) => names,
+                Err(e) => {
+                    warn!(
+                        "Could not read the modules in use from {}, only comparing the ones in \
+                         use now: {e}",
+                        in_use_file(&old_cache)
+                    );
+                    forge_names.iter().cloned().collect()
+                }
+            };
+            let mut old_api =
+                forge::ForgeApi::new(Some(old_cache), &args.forge_url, args.forge_token.clone());
+            old_api.use_snapshot();
+            old_api.include_prereleases(args.include_prereleases);
+            let rows =
+                report::changes_since_rows(&api, &old_api, &forge_names, &old_names, &failed_names);
+            print_changes(&mut out, &args, &api, &rows).unwrap();
+        }
+        View::ForgeLatest => {
+            let columns = report::LatestColumns {
+                endorsement: args.show_endorsement,
+                puppet_version: args.puppet_version.as_ref(),
+                os: &args.os,
+                repo_usage: &repo_usage,
+            };
+            let table = report::latest_table(
+                &api,
+                &forge_names,
+                &failed_names,
+                &columns,
+                args.limit,
+                stale_before,
+            );
+            if args.fail_on.contains(&FailOn::UnsupportedOs) {
+                violations.extend(table.missing_os);
+            }
+            let mut counts = format!("{} modules, {} deprecated", table.total, table.deprecated);
+            if let Some(ref puppet) = args.puppet_version {
+                counts.push_str(&format!(
+                    ", {} not supporting Puppet {puppet}",
+                    table.unsupported
+                ));
+            }
+            print_details_start(&mut out, args.format, args.md_style, &counts).unwrap();
+            print_version_table(
+                &mut out,
+                args.format,
+                args.md_style,
+                &naming,
+                &palette,
+                args.max_width,
+                &table.header,
+                &table.rows,
+                None,
+            )
+            .unwrap();
+            print_limit_footer(&mut out, args.format, args.limit, table.total, "modules").unwrap();
+            print_details_end(&mut out, args.format, args.md_style).unwrap();
+        }
+        View::ForgeBranches => {
+            // indices of the columns to show, in the standard layout: name, Forge, branches
+            let mut column_names = vec!["name", "forge"];
+            column_names.extend(
+                shown_branches
+                    .iter()
+                    .map(|bn| short_branch_name(bn, &prefix)),
+            );
+            let columns: Vec<usize> = if args.columns.is_empty() {
+                (0..column_names.len()).collect()
+            } else {
+                let unknown: Vec<&str> = args
+                    .columns
+                    .iter()
+                    .filter(|column| !column_names.contains(&column.as_str()))
+                    .map(String::as_str)
+                    .collect();
+                if !unknown.is_empty() {
+                    eprintln!(
+                        "Sorry, the column(s) {} are not known. Columns to choose from: {}",
+                        unknown.join(", "),
+                        column_names.join(", ")
+                    );
+                    process::exit(1);
+                }
+                args.columns
+                    .iter()
+                    .filter_map(|column| column_names.iter().position(|name| name == column))
+                    .collect()
+            };
+
+            let mut module_rows = report::module_rows(
+                &api,
+                &forge_names,
+                &failed_names,
+                &branch_modules,
+                stale_before,
+            );
+            report::warn_replacements(&api, &module_rows);
+
+            // module_rows is sorted by name at this point, the sorts are stable so modules with
+            // the same rank stay in alphabetical order
+            match args.sort {
+                SortOrder::Name => (),
+                SortOrder::Lag => module_rows.sort_by_key(|row| std::cmp::Reverse(row.lag())),
+                SortOrder::Deprecated => module_rows.sort_by_key(|row| !row.forge_deprecated),
+            };
+            // check the policies before limiting, the limit is only about what is displayed
+            let policy = report::Policy {
+                deprecated: args.fail_on.contains(&FailOn::Deprecated),
+                max_behind: args
+                    .fail_on
+                    .contains(&FailOn::Outdated)
+                    .then(|| args.max_behind.unwrap_or_default()),
+                below_minimum: args.fail_on.contains(&FailOn::BelowMinimum),
+                min_versions: args
+                    .min_version
+                    .iter()
+                    .map(|(module, version)| (module.as_str(), version))
+                    .collect(),
+            };
+            let (figures, found) =
+                report::check_policies(&module_rows, &shown_branches, &prefix, &policy);
+            violations.extend(found);
+            summary = Some(figures);
+
+            let total = module_rows.len();
+            if let Some(limit) = args.limit {
+                module_rows.truncate(limit);
+            }
+
+            // one object per module instead of a table, with the selected columns
+            if args.format == OutputFormat::Ndjson {
+                for mod_row in &module_rows {
+                    let mut line = serde_json::Map::new();
+                    if columns.contains(&0) {
+                        line.insert(
+                            "name".to_string(),
+                            args.name_style.display(&mod_row.name).into(),
+                        );
+                    }
+                    if columns.contains(&1) {
+                        line.insert(
+                            "forge_latest".to_string(),
+                            mod_row
+                                .forge_version
+                                .as_ref()
+                                .map(Version::to_string)
+                                .into(),
+                        );
+                        line.insert("lookup_failed".to_string(), mod_row.lookup_failed.into());
+                        line.insert("deprecated".to_string(), mod_row.forge_deprecated.into());
+                    }
+                    let branches: serde_json::Map<String, serde_json::Value> = columns
+                        .iter()
+                        .filter(|&&col| col >= 2)
+                        .filter_map(|&col| {
+                            let branch_name = &shown_branches[col - 2];
+                            let version = mod_row.branch_versions.get(branch_name)?.as_ref()?;
+                            Some((
+                                short_branch_name(branch_name, &prefix).to_string(),
+                                version.to_string().into(),
+                            ))
+                        })
+                        .collect();
+                    line.insert("branches".to_string(), branches.into());
+                    writeln!(out, "{}", serde_json::Value::Object(line)).unwrap();
+                }
+            }
+
+            // the forge version with freshness-indicators, followed by one cell per branch
+            // (iterate by branch to be consistent with the headers)
+            let (mut module_cells, mut branch_totals) = report::branch_cells(
+                module_rows,
+                &shown_branches,
+                baseline,
+                &policy.min_versions,
+                args.show_target,
+            );
+            // the git modules are compared among the branches
+            if args.git_tags || args.include_git {
+                let git_rows = report::git_rows(
+                    &branch_modules,
+                    &shown_branches,
+                    args.include_git,
+                    &mut branch_totals,
+                );
+                for (git_name, version_cells) in git_rows {
+                    module_cells.push((TableCell::Text(git_name), version_cells));
+                }
+            }
+            // the Forge modules, before the cells are moved into rows
+            let module_names: Vec<String> = module_cells
+                .iter()
+                .filter_map(|(name, _)| match name {
+                    TableCell::Module(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            let totals_text: Vec<String> = branch_totals
+                .iter()
+                .map(|(current, outdated)| format!("{current} current, {outdated} outdated"))
+                .collect();
+
+            // the number of modules tells branches apart at a glance, even if all are current
+            let branch_labels: Vec<String> = std::iter::once("Forge latest".to_string())
+                .chain(shown_branches.iter().map(|bname| {
+                    let count = branch_modules
+                        .iter()
+                        .find(|bm| &bm.name == bname)
+                        .map_or(0, |bm| bm.modules.len());
+                    format!("{} ({count})", short_branch_name(bname, &prefix))
+                }))
+                .collect();
+            let (header, rows, footer) = if args.transpose {
+                // one row per branch (and one for the Forge), one column per module
+                let mut header = vec![TableCell::Text("Branch".to_string())];
+                header.extend(module_cells.iter().map(|(name, _)| name.clone()));
+                if args.totals {
+                    header.push(TableCell::Text("Total".to_string()));
+                }
+                let rows: Vec<Vec<TableCell>> = branch_labels
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, label)| {
+                        let mut row = vec![TableCell::Text(label.to_string())];
+                        row.extend(
+                            module_cells
+                                .iter_mut()
+                                .map(|(_, cells)| TableCell::Version(cells[idx].take())),
+                        );
+                        if args.totals {
+                            // the Forge row has no totals
+                            let total = idx.checked_sub(1).map(|i| totals_text[i].clone());
+                            row.push(TableCell::Text(total.unwrap_or_default()));
+                        }
+                        row
+                    })
+                    .collect();
+                (header, rows, None)
+            } else {
+                let mut header = vec![TableCell::Text("Module-Name".to_string())];
+                header.extend(
+                    branch_labels
+                        .iter()
+                        .map(|label| TableCell::Text(label.to_string())),
+                );
+                let rows: Vec<Vec<TableCell>> = module_cells
+                    .into_iter()
+                    .map(|(name, cells)| {
+                        std::iter::once(name)
+                            .chain(cells.into_iter().map(TableCell::Version))
+                            .collect()
+                    })
+                    .collect();
+                let footer = args.totals.then(|| {
+                    let mut footer = vec![
+                        TableCell::Text("Total".to_string()),
+                        TableCell::Text(String::new()),
+                    ];
+                    footer.extend(totals_text.into_iter().map(TableCell::Text));
+                    footer
+                });
+                (header, rows, footer)
+            };
+            // pick the columns, or the rows of the Forge and branches if transposed
+            let (header, rows, footer) = if args.transpose {
+                let rows: Vec<Vec<TableCell>> = columns
+                    .iter()
+                    .filter_map(|idx| idx.checked_sub(1))
+                    .map(|idx| rows[idx].clone())
+                    .collect();
+                (header, rows, footer)
+            } else {
+                let pick = |row: Vec<TableCell>| -> Vec<TableCell> {
+                    columns.iter().map(|&idx| row[idx].clone()).collect()
+                };
+                (
+                    pick(header),
+                    rows.into_iter().map(pick).collect(),
+                    footer.map(pick),
+                )
+            };
+            if args.format == OutputFormat::Ndjson {
+                // the modules have been written one by one above
+            } else if args.tui {
+                // what is shown when pressing enter on a module
+                // there is nothing to show for the modules that could not be looked up
+                let details: HashMap<String, tui::Details> = module_names
+                    .into_iter()
+                    .filter(|name| !failed_names.contains(name))
+                    .map(|name| {
+                        let details = report::module_details(&api, &name);
+                        (args.name_style.display(&name), details)
+                    })
+                    .collect();
+                // the browser shows the cells as they are, the names are looked up the same way
+                let styled = |row: Vec<TableCell>| -> Vec<TableCell> {
+                    row.into_iter()
+                        .map(|cell| match cell {
+                            TableCell::Module(name) => {
+                                TableCell::Module(args.name_style.display(&name))
+                            }
+                            cell => cell,
+                        })
+                        .collect()
+                };
+                let header = styled(header);
+                let rows: Vec<Vec<TableCell>> = rows.into_iter().map(styled).collect();
+                let footer = footer.map(styled);
+                if let Err(e) = tui::browse(&header, &rows, footer.as_deref(), &details, &palette) {
+                    error!("Error showing the interactive table: {e}");
+                    process::exit(1);
+                }
+            } else {
+                let counts = summary.as_ref().map_or(String::new(), |figures| {
+                    format!(
+                        "{} modules in use, {} outdated, {} deprecated",
+                        figures.modules, figures.outdated, figures.deprecated
+                    )
+                });
+                print_details_start(&mut out, args.format, args.md_style, &counts).unwrap();
+                print_branch_table(
+                    &mut out,
+                    &args,
+                    &naming,
+                    &palette,
+                    &columns,
+                    &header,
+                    &rows,
+                    footer.as_deref(),
+                )
+                .unwrap();
+                print_limit_footer(&mut out, args.format, args.limit, total, "modules").unwrap();
+                if !args.no_legend {
+                    print_legend(
+                        &mut out,
+                        args.format,
+                        args.md_style,
+                        baseline,
+                        &palette,
+                        &statuses,
+                    )
+                    .unwrap();
+                }
+                if args.show_commit_info {
+                    let branches: Vec<&models::BranchMeta> = shown_branches
+                        .iter()
+                        .filter_map(|bname| branch_modules.iter().find(|bm| &bm.name == bname))
+                        .collect();
+                    print_commit_info(&mut out, args.format, &prefix, &branches).unwrap();
+                }
+                print_details_end(&mut out, args.format, args.md_style).unwrap();
+            }
+        }
+        View::ForgeDeprecated => {
+            let branches: Vec<&models::BranchMeta> = shown_branches
+                .iter()
+                .filter_map(|bname| branch_modules.iter().find(|bm| &bm.name == bname))
+                .collect();
+            let mut rows: Vec<Vec<String>> =
+                report::deprecated_modules(&api, &forge_names, &branches)
+                    .into_iter()
+                    .map(|module| {
+                        let branches: Vec<&str> = module
+                            .branches
+                            .iter()
+                            .map(|bname| short_branch_name(bname, &prefix))
+                            .collect();
+                        vec![
+                            args.name_style.display(module.name),
+                            module
+                                .deprecation
+                                .at
+                                .map(|at| at.format("%Y-%m-%d").to_string())
+                                .unwrap_or_default(),
+                            module.deprecation.reason.unwrap_or_default(),
+                            module.deprecation.replacement.unwrap_or_default(),
+                            branches.join(", "),
+                        ]
+                    })
+                    .collect();
+            let total = rows.len();
+            if let Some(limit) = args.limit {
+                rows.truncate(limit);
+            }
+            print_plain_table(
+                &mut out,
+                args.format,
+                "deprecated",
+                &[
+                    "Module-Name",
+                    "Deprecated",
+                    "Reason",
+                    "Replacement",
+                    "Branches",
+                ],
+                &rows,
+            )
+            .unwrap();
+            print_limit_footer(&mut out, args.format, args.limit, total, "modules").unwrap();
+        }
+        View::ForgeReleases { .. }
+        | View::Drift { .. }
+        | View::Doctor
+        | View::Completions { .. } => {
+            unreachable!("handled before parsing the repository")
+        }
+        View::Bump {
+            ref branch,
+            dry_run,
+        } => {
+            let branch_meta = find_branch(&branch_modules, &branch_names, &prefix, branch);
+            let bumped = report::bump_to_latest(&api, branch_meta);
+            if dry_run {
+                for bump in &bumped.bumps {
+                    writeln!(out, "@@ line {} @@", bump.line).unwrap();
+                    writeln!(out, "-{}", bump.old_line).unwrap();
+                    writeln!(out, "+{}", bump.new_line).unwrap();
+                }
+            } else {
+                write!(out, "{}", bumped.content).unwrap();
+            }
+        }
+        View::Conflicts { ref branch } => {
+            let branch_meta = find_branch(&branch_modules, &branch_names, &prefix, branch);
+            let mut rows: Vec<Vec<String>> = vec![];
+            for conflict in report::conflicts(&api, branch_meta) {
+                let dependency = args.name_style.display(&conflict.dependency);
+                let module = |requirement: &report::Requirement| {
+                    format!(
+                        "{} {}",
+                        args.name_style.display(&requirement.module),
+                        requirement.version
+                    )
+                };
+                match conflict.used {
+                    Some(version) => {
+                        for requirement in &conflict.required_by {
+                            rows.push(vec![
+                                dependency.clone(),
+                                module(requirement),
+                                requirement.text.clone(),
+                                format!("branch uses {version}"),
+                            ]);
+                        }
+                    }
+                    None => rows.push(vec![
+                        dependency,
+                        conflict
+                            .required_by
+                            .iter()
+                            .map(module)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        conflict
+                            .required_by
+                            .iter()
+                            .map(|requirement| requirement.text.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" / "),
+                        "no release satisfies all".to_string(),
+                    ]),
+                }
+            }
+            rows.sort();
+            print_plain_table(
+                &mut out,
+                args.format,
+                "conflicts",
+                &["Dependency", "Required by", "Requirement", "Conflict"],
+                &rows,
+            )
+            .unwrap();
+        }
+        View::Graph { ref branch } => {
+            let branch_meta = find_branch(&branch_modules, &branch_names, &prefix, branch);
+            let graph = match report::dependency_graph(&api, branch_meta, args.fail_fast) {
+                Ok(graph) => graph,
+                Err((name, e)) => {
+                    error!("Error looking up {name}: {e}");
+                    process::exit(1);
+                }
+            };
+            writeln!(out, "digraph \"{}\" {{", dot_escape(branch)).unwrap();
+            writeln!(out, "  node [shape=box];").unwrap();
+            for (name, version, deprecated) in &graph.modules {
+                let style = if *deprecated {
+                    ", style=filled, fillcolor=\"#f4cccc\", color=red"
+                } else {
+                    ""
+                };
+                writeln!(
+                    out,
+                    "  \"{}\" [label=\"{}\\n{version}\"{style}];",
+                    dot_escape(name),
+                    dot_escape(name)
+                )
+                .unwrap();
+            }
+            for edge in &graph.edges {
+                writeln!(
+                    out,
+                    "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                    dot_escape(&edge.module),
+                    dot_escape(&edge.dependency),
+                    dot_escape(&edge.requirement),
+                    if edge.unmet {
+                        ", color=red, fontcolor=red"
+                    } else {
+                        ""
+                    }
+                )
+                .unwrap();
+            }
+            // dependencies that are not part of the branch
+            for name in &graph.missing {
+                writeln!(
+                    out,
+                    "  \"{}\" [label=\"{}\\nnot in branch\", style=dashed];",
+                    dot_escape(name),
+                    dot_escape(name)
+                )
+                .unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+            failed_lookups.extend(graph.failed);
+        }
+        View::Modules => {
+            // forge_names covers all branches, but `-b` asks for a subset
+            for name in forge_names.iter().filter(|name| {
+                branch_modules
+                    .iter()
+                    .filter(|bm| shown_branches.contains(&bm.name))
+                    .flat_map(|bm| &bm.modules)
+                    .any(|module| matches!(module, models::Module::Forge(n, _) if n == *name))
+            }) {
+                writeln!(out, "{name}").unwrap();
+            }
+        }
+        View::GitModules => {
+            let branches: Vec<&models::BranchMeta> = shown_branches
+                .iter()
+                .filter_map(|bname| branch_modules.iter().find(|bm| &bm.name == bname))
+                .collect();
+            let rows = report::git_modules(&branches, &prefix);
+            print_plain_table(
+                &mut out,
+                args.format,
+                "git-modules",
+                &["Branch", "Module", "URL", "Reference", "Install path"],
+                &rows,
+            )
+            .unwrap();
+        }
+        View::Orphans => {
+            let branches: Vec<&models::BranchMeta> = shown_branches
+                .iter()
+                .filter_map(|bname| branch_modules.iter().find(|bm| &bm.name == bname))
+                .collect();
+            let mut rows: Vec<Vec<String>> = report::orphans(&branches)
+                .into_iter()
+                .map(|(module, branch)| {
+                    let (name, version) = match module {
+                        models::Module::Forge(name, version) => {
+                            (args.name_style.display(name), version.to_string())
+                        }
+                        models::Module::Git(name, spec) => {
+                            (name.clone(), spec.reference.to_string())
+                        }
+                    };
+                    vec![
+                        name,
+                        short_branch_name(branch, &prefix).to_string(),
+                        version,
+                    ]
+                })
+                .collect();
+            rows.sort();
+            let total = rows.len();
+            if let Some(limit) = args.limit {
+                rows.truncate(limit);
+            }
+            print_plain_table(
+                &mut out,
+                args.format,
+                "orphans",
+                &["Module-Name", "Branch", "Version"],
+                &rows,
+            )
+            .unwrap();
+            print_limit_footer(&mut out, args.format, args.limit, total, "modules").unwrap();
+        }
+        View::Identical => {
+            let branches: Vec<&models::BranchMeta> = shown_branches
+                .iter()
+                .filter_map(|bname| branch_modules.iter().find(|bm| &bm.name == bname))
+                .collect();
+            let mut rows: Vec<Vec<String>> = models::identical_branches(&branches)
+                .into_iter()
+                .map(|group| {
+                    let names: Vec<&str> = group
+                        .iter()
+                        .map(|branch| short_branch_name(&branch.name, &prefix))
+                        .collect();
+                    vec![names.join(", "), group[0].modules.len().to_string()]
+                })
+                .collect();
+            rows.sort();
+            let total = rows.len();
+            if let Some(limit) = args.limit {
+                rows.truncate(limit);
+            }
+            print_plain_table(
+                &mut out,
+                args.format,
+                "identical",
+                &["Branches", "Modules"],
+                &rows,
+            )
+            .unwrap();
+            print_limit_footer(&mut out, args.format, args.limit, total, "groups").unwrap();
+        }
+    };
+
+    out.flush().unwrap();
+    timings.add("Showing the view", view_started.elapsed());
+    drop(view_span);
+
+    // the snapshot must not end up in the cache, its data may be old
+    if args.snapshot.is_none() {
+        store_cache(&api, &shellexpand::tilde(&args.cache_file));
+        if matches!(args.view, View::ForgeLatest) {
+            let path = in_use_file(&args.cache_file);
+            if let Err(e) = std::fs::write(&path, in_use) {
+                warn!("Could not write the modules in use to {path}: {e}");
+            }
+        }
+    }
+
+    if let Some(ref path) = args.summary_file {
+        match summary {
+            Some(mut summary) => {
+                summary.violations = violations.len();
+                summary.passed = violations.is_empty();
+                let written = File::create(shellexpand::tilde(path).as_ref())
+                    .and_then(|mut f| writeln!(f, "{}", serde_json::to_string(&summary).unwrap()));
+                if let Err(e) = written {
+                    error!("Error writing summary file {path}: {e}");
+                    process::exit(1);
+                }
+            }
+            None => warn!("This view does not support --summary-file, not writing {path}"),
+        }
+    }
+
+    if args.timings {
+        timings.add("Total", started.elapsed());
+        for (step, elapsed) in &timings.steps {
+            eprintln!("{step:<24}{:>10.3}s", elapsed.as_secs_f64());
+        }
+        let (hits, misses) = api.cache_stats();
+        eprintln!("Forge cache: {hits} hits, {misses} misses");
+    }
+
+    if !failed_lookups.is_empty() {
+        error!(
+            "{} modules could not be looked up on the Forge:",
+            failed_lookups.len()
+        );
+        for (name, e) in &failed_lookups {
+            error!("\t{name}: {e}");
+        }
+    }
+    for violation in &violations {
+        eprintln!("{violation}");
+    }
+    // an incomplete report may hide violations, that is the bigger problem
+    if !failed_lookups.is_empty() {
+        process::exit(1);
+    }
+    if !violations.is_empty() {
+        process::exit(2);
+    }
+}
+
+/// Runs the checks of the doctor view and prints their results, returns whether all of them
+/// passed. Remote repositories are not checked, as that would mean cloning them.
+fn doctor(args: &Cli) -> bool {
+    let mut rows: Vec<Vec<String>> = vec![];
+    let mut check = |name: String, result: Result<String, String>| {
+        let (status, details) = match result {
+            Ok(details) => ("pass", details),
+            Err(details) => ("FAIL", details),
+        };
+        rows.push(vec![name, status.to_string(), details]);
+    };
+
+    let repo_args = match args.repo.is_empty() {
+        true => vec![String::from(".")],
+        false => args.repo.clone(),
+    };
+    for label in repo_args.iter().filter(|repo| !is_remote_url(repo)) {
+        let repo = match repo::open_repo(&shellexpand::tilde(label)) {
+            Ok(r) => {
+                let path = r.path().display().to_string();
+                check(format!("Open {label}"), Ok(path));
+                r
+            }
+            Err(e) => {
+                check(format!("Open {label}"), Err(e.message().to_string()));
+                continue;
+            }
+        };
+        // same rules as `repo::parse_git_repo`, including the local branches of bare clones
+        let prefix = format!("{}/", args.remote);
+        let count = |branch_type| {
+            repo.branches(Some(branch_type))
+                .map(|branches| {
+                    branches
+                        .filter_map(|b| b.ok())
+                        // skips `origin/HEAD`, which points to another branch
+                        .filter(|(branch, _)| {
+                            branch.get().kind() == Some(git2::ReferenceType::Direct)
+                        })
+                        .filter_map(|(branch, _)| branch.name().ok().flatten().map(String::from))
+                        .filter(|name| {
+                            branch_type == BranchType::Local || name.starts_with(&prefix)
+                        })
+                        .count()
+                })
+                .unwrap_or_default()
+        };
+        let remote_branches = repo
+            .branches(Some(BranchType::Remote))
+            .is_ok_and(|mut branches| branches.next().is_some());
+        let found = match repo.is_bare() && !remote_branches {
+            true => count(BranchType::Local),
+            false => count(BranchType::Remote),
+        };
+        let result = if found > 0 {
+            Ok(format!("{found} branches"))
+        } else {
+            let remotes: Vec<String> = repo
+                .remotes()
+                .map(|remotes| remotes.iter().flatten().map(String::from).collect())
+                .unwrap_or_default();
+            match remotes.is_empty() {
+                true => Err(String::from("the repository has no remotes")),
+                false => Err(format!("no branches, remotes: {}", remotes.join(", "))),
+            }
+        };
+        check(format!("Branches of {} in {label}", args.remote), result);
+    }
+
+    let forges = std::iter::once((args.forge_url.clone(), args.forge_token.clone())).chain(
+        load_forge_config(args)
+            .forge
+            .into_iter()
+            .map(|author_forge| (author_forge.url, author_forge.token)),
+    );
+    for (url, token) in forges {
+        let api = forge::ForgeApi::new(None, &url, token);
+        check(
+            format!("Reach {url}"),
+            api.ping()
+                .map(|status| format!("HTTP {status}"))
+                .map_err(|e| e.to_string()),
+        );
+    }
+
+    // appending leaves an existing cache as it is, a file created just for the check is removed
+    let cache_file = shellexpand::tilde(&args.cache_file).into_owned();
+    let parse_cache = parse_cache_file(&args.cache_file);
+    for path in [cache_file.as_str(), parse_cache.as_str()] {
+        let existed = std::path::Path::new(path).exists();
+        let result = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path);
+        if !existed && result.is_ok() {
+            let _ = std::fs::remove_file(path);
+        }
+        check(
+            format!("Write {path}"),
+            result.map(|_| String::new()).map_err(|e| e.to_string()),
+        );
+    }
+
+    let passed = rows.iter().all(|row| row[1] == "pass");
+    let mut out = io::stdout();
+    print_plain_table(
+        &mut out,
+        args.format,
+        "doctor",
+        &["Check", "Result", "Details"],
+        &rows,
+    )
+    .unwrap();
+    passed
+}
+
+/// Reads the file given with `--forge-config`, or exits if it is broken
+fn load_forge_config(args: &Cli) -> forge::ForgeConfig {
+    let Some(ref path) = args.forge_config else {
+        return forge::ForgeConfig::default();
+    };
+    let parsed = std::fs::read_to_string(shellexpand::tilde(path).as_ref())
+        .map_err(|e| e.to_string())
+        .and_then(|contents| forge::ForgeConfig::parse(&contents).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Error reading the Forge configuration {path}: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Writes the cache. Failing to do so (e.g. in a read-only container) doesn't make the results any
+/// less valid, so it is only logged.
+fn store_cache(api: &forge::ForgeApi, cache_file: &str) {
+    if let Err(e) = api.store_cache(cache_file) {
+        warn!("Could not write the cache to {cache_file}: {e}");
+    }
+}
+
+/// Returns the branch `name` (without `prefix`) for the views working on a single branch, or
+/// exits with a list of the known branches.
+fn find_branch<'a>(
+    branch_modules: &'a [models::BranchMeta],
+    branch_names: &[String],
+    prefix: &str,
+    name: &str,
+) -> &'a models::BranchMeta {
+    let full_name = full_branch_name(name, prefix);
+    match branch_modules.iter().find(|bm| bm.name == full_name) {
+        Some(bm) => bm,
+        None => {
+            eprintln!("Sorry, the branch {name} is not known. Branches to choose from:");
+            for branch in branch_names {
+                eprintln!("\t{}", short_branch_name(branch, prefix));
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// A file kept next to the Forge cache `cache_file`, e.g. `/tmp/asdf-puppetfiles.json` for
+/// `/tmp/asdf.json` and `what` being `puppetfiles`
+fn cache_sidecar(cache_file: &str, what: &str) -> String {
+    let cache_file = shellexpand::tilde(cache_file);
+    let path = std::path::Path::new(cache_file.as_ref());
+    let stem = path
+        .file_stem()
+        .map_or("puppetstuff".into(), |stem| stem.to_string_lossy());
+    path.with_file_name(format!("{stem}-{what}.json"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Where the parsed `Puppetfile`s of the branches are kept between runs
+fn parse_cache_file(cache_file: &str) -> String {
+    cache_sidecar(cache_file, "puppetfiles")
+}
+
+/// Where `forge-latest` keeps the names of the Forge modules in use, which `--changes-since`
+/// compares against. The cache itself keeps modules that are long gone or were left out by `-m`.
+fn in_use_file(cache_file: &str) -> String {
+    cache_sidecar(cache_file, "in-use")
+}
+
+/// Reports why the `Puppetfile`s of a repository could not be read and exits
+fn exit_repo_error(e: RepoError) -> ! {
+    match e {
+        // an empty table would leave the user wondering what went wrong
+        RepoError::NoBranches { .. } => {
+            eprintln!("{e}");
+            process::exit(3);
+        }
+        RepoError::Bare => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        _ => {
+            error!("{e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Shows the view again whenever the `Puppetfile` in the working tree of `repo_path` changes
+/// (`--watch`). Each run is a run of this program without `--watch`, which leaves the Forge cache
+/// warm for the next one. Never returns.
+fn watch(repo_path: &str, puppetfile_path: &str, working: bool) -> ! {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let (_, file) = repo::open_repo(repo_path)
+        .map_err(RepoError::Open)
+        .and_then(|repo| repo::working_puppetfile(&repo, puppetfile_path))
+        .unwrap_or_else(|e| exit_repo_error(e));
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            error!("Error finding the path of this program: {e}");
+            process::exit(1);
+        }
+    };
+    let mut run_args: Vec<std::ffi::OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--watch")
+        .collect();
+    if !working {
+        run_args.insert(0, "--working".into());
+    }
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(w) => w,
+        Err(e) => {
+            error!("Error watching {}: {e}", file.display());
+            process::exit(1);
+        }
+    };
+    // editors often replace the file instead of writing to it, which only shows in its directory
+    let dir = file.parent().unwrap_or(std::path::Path::new("."));
+    if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+        error!("Error watching {}: {e}", dir.display());
+        process::exit(1);
+    }
+    loop {
+        // clear the terminal and start at the top
+        print!("\x1b[2J\x1b[H");
+        io::stdout().flush().unwrap();
+        if let Err(e) = process::Command::new(&exe).args(&run_args).status() {
+            error!("Error running {}: {e}", exe.display());
+        }
+        eprintln!(
+            "Watching {} for changes, press Ctrl-C to stop",
+            file.display()
+        );
+        // reading the file does not count as a change
+        loop {
+            match rx.recv() {
+                Ok(Ok(event))
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        && event
+                            .paths
+                            .iter()
+                            .any(|path| path.file_name() == file.file_name()) =>
+                {
+                    break
+                }
+                Ok(Ok(_)) => (),
+                Ok(Err(e)) => warn!("Error watching {}: {e}", file.display()),
+                Err(_) => process::exit(1),
+            }
+        }
+        // a save often comes as several events, wait for them to settle
+        while rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+    }
+}
+
+/// Parses `--since`, either a date (`2024-10-01`) or a number of days or weeks ago (`30d`, `6w`)
+fn parse_since(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+    let days_per_unit = match value.chars().last() {
+        Some('d') => 1,
+        Some('w') => 7,
+        _ => return Err("expected a date like 2024-10-01 or a duration like 30d or 6w".into()),
+    };
+    let count: i64 = value[..value.len() - 1]
+        .parse()
+        .map_err(|e| format!("'{value}' is not a valid duration: {e}"))?;
+    Ok(chrono::Utc::now() - chrono::Duration::days(count * days_per_unit))
+}
+
+/// Parses `--puppet-version`, missing components are zero (`8` is `8.0.0`)
+fn parse_puppet_version(value: &str) -> Result<Version, String> {
+    let padded = match value.matches('.').count() {
+        0 => format!("{value}.0.0"),
+        1 => format!("{value}.0"),
+        _ => value.to_string(),
+    };
+    Version::parse(&padded).map_err(|e| format!("'{value}' is not a valid version: {e}"))
+}
+
+/// Parses `--os` in the form `<name>[:<release>]`
+fn parse_os(value: &str) -> Result<(String, Option<String>), String> {
+    let (name, release) = match value.split_once(':') {
+        Some((name, release)) => (name, Some(release.to_string())),
+        None => (value, None),
+    };
+    if name.is_empty() || release.as_deref() == Some("") {
+        return Err("expected <name>[:<release>], e.g. RedHat:9".into());
+    }
+    Ok((name.to_string(), release))
+}
+
+/// Parses `--min-version` in the form `<module>=<version>`
+fn parse_min_version(value: &str) -> Result<(String, Version), String> {
+    let (module, version) = value
+        .split_once('=')
+        .ok_or("expected <module>=<version>, e.g. puppetlabs-stdlib=8.0.0")?;
+    let version =
+        Version::parse(version).map_err(|e| format!("'{version}' is not a valid version: {e}"))?;
+    Ok((module.replacen('/', "-", 1), version))
+}
+
+/// Parses `--status-color` in the form `<status>=<colour>`, the colour being the name of a terminal
+/// colour, `#rrggbb` or `none`
+fn parse_status_color(value: &str) -> Result<(Status, Option<Color>), String> {
+    let (status, color) = value
+        .split_once('=')
+        .ok_or("expected <status>=<colour>, e.g. deprecated=dark-red")?;
+    let status = StatusArg::from_str(status, true).map_err(|_| {
+        let known: Vec<String> = StatusArg::value_variants()
+            .iter()
+            .filter_map(|s| Some(s.to_possible_value()?.get_name().to_string()))
+            .collect();
+        format!(
+            "unknown status '{status}', expected one of {}",
+            known.join(", ")
+        )
+    })?;
+    let status = Status::from(status);
+    let color = match color.to_lowercase().as_str() {
+        "none" => None,
+        "black" => Some(Color::Black),
+        "dark-grey" => Some(Color::DarkGrey),
+        "red" => Some(Color::Red),
+        "dark-red" => Some(Color::DarkRed),
+        "green" => Some(Color::Green),
+        "dark-green" => Some(Color::DarkGreen),
+        "yellow" => Some(Color::Yellow),
+        "dark-yellow" => Some(Color::DarkYellow),
+        "blue" => Some(Color::Blue),
+        "dark-blue" => Some(Color::DarkBlue),
+        "magenta" => Some(Color::Magenta),
+        "dark-magenta" => Some(Color::DarkMagenta),
+        "cyan" => Some(Color::Cyan),
+        "dark-cyan" => Some(Color::DarkCyan),
+        "white" => Some(Color::White),
+        "grey" => Some(Color::Grey),
+        hex if hex.len() == 7
+            && hex.starts_with('#')
+            && hex[1..].bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            // only hex digits, so every channel is valid
+            let channel = |range| u8::from_str_radix(&hex[range], 16).unwrap();
+            Some(Color::Rgb {
+                r: channel(1..3),
+                g: channel(3..5),
+                b: channel(5..7),
+            })
+        }
+        _ => return Err(format!("'{color}' is not a valid colour")),
+    };
+    Ok((status, color))
+}
+
+/// Parses `--max-behind` in the form `<major|minor|patch>:<N>`
+fn parse_max_behind(value: &str) -> Result<models::VersionLag, String> {
+    let (component, count) = value
+        .split_once(':')
+        .ok_or("expected <major|minor|patch>:<N>, e.g. minor:2")?;
+    let count: u64 = count
+        .parse()
+        .map_err(|e| format!("'{count}' is not a number: {e}"))?;
+    let mut lag = models::VersionLag::default();
+    match component {
+        "major" => lag.major = count,
+        "minor" => lag.minor = count,
+        "patch" => lag.patch = count,
+        other => return Err(format!("unknown version component '{other}'")),
+    };
+    Ok(lag)
+}
+
+/// Whether `repo` looks like the URL of a remote repository rather than a local path, i.e. it has a
+/// scheme (`https://`, `ssh://`, `file://`) or is in scp-like syntax (`git@host:path`).
+fn is_remote_url(repo: &str) -> bool {
+    static SCP_LIKE_RE: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"^[\w.-]+@[\w.-]+:").unwrap());
+    repo.contains("://") || SCP_LIKE_RE.is_match(repo)
+}
+
+/// Builds the regex for `--module`: a regex if enclosed in slashes, a glob otherwise. Globs are
+/// matched against the whole normalized name, so `puppetlabs/*` works as well.
+fn module_pattern(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    if let Some(re) = pattern
+        .strip_prefix('/')
+        .and_then(|p| p.strip_suffix('/'))
+        .filter(|p| !p.is_empty())
+    {
+        return regex::Regex::new(re);
+    }
+    glob_regex(&pattern.replace('/', "-"))
+}
+
+/// Translates a glob with `*` and `?` into an anchored regex
+fn glob_regex(glob: &str) -> Result<regex::Regex, regex::Error> {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(&re)
+}
+
+/// Prints the modules `names` along with the URL that would be requested for each of them, for
+/// `--plan`
+fn print_plan(
+    out: &mut dyn Write,
+    args: &Cli,
+    api: &forge::ForgeApi,
+    names: &[String],
+) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = names
+        .iter()
+        .map(|name| vec![args.name_style.display(name), api.module_url(name)])
+        .collect();
+    print_plain_table(out, args.format, "plan", &["Module-Name", "URL"], &rows)
+}
+
+/// Prints the table of the branches view, split into pages of `--page-branches` branches.
+/// `columns` are the columns of the untransposed table that were picked, 0 and 1 being the module
+/// names and the Forge's versions.
+#[allow(clippy::too_many_arguments)]
+fn print_branch_table(
+    out: &mut dyn Write,
+    args: &Cli,
+    naming: &ModuleNames,
+    palette: &Palette,
+    columns: &[usize],
+    header: &[TableCell],
+    rows: &[Vec<TableCell>],
+    footer: Option<&[TableCell]>,
+) -> io::Result<()> {
+    // the module names and the Forge's versions are repeated on each page
+    let (fixed, branch_columns): (Vec<usize>, Vec<usize>) = match args.page_branches {
+        // `--page-branches` conflicts with `--transpose`, whose columns are modules
+        Some(_) => (0..header.len()).partition(|&col| columns[col] < 2),
+        None => (vec![], vec![]),
+    };
+    let per_page = args.page_branches.map_or(1, usize::from);
+    let pages: Vec<&[usize]> = branch_columns.chunks(per_page).collect();
+    if pages.len() <= 1 {
+        return print_version_table(
+            out,
+            args.format,
+            args.md_style,
+            naming,
+            palette,
+            args.max_width,
+            header,
+            rows,
+            footer,
+        );
+    }
+    for (idx, page) in pages.iter().enumerate() {
+        let page_columns: Vec<usize> = fixed.iter().chain(page.iter()).copied().collect();
+        let pick = |row: &[TableCell]| -> Vec<TableCell> {
+            page_columns.iter().map(|&col| row[col].clone()).collect()
+        };
+        let first = idx * per_page + 1;
+        print_page_heading(
+            out,
+            args.format,
+            &format!(
+                "Branches {first}–{} of {} (page {}/{})",
+                first + page.len() - 1,
+                branch_columns.len(),
+                idx + 1,
+                pages.len()
+            ),
+        )?;
+        print_version_table(
+            out,
+            args.format,
+            args.md_style,
+            naming,
+            palette,
+            args.max_width,
+            &pick(header),
+            &rows.iter().map(|row| pick(row)).collect::<Vec<_>>(),
+            footer.map(pick).as_deref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints the rows built by `change_row`
+fn print_changes(
+    out: &mut dyn Write,
+    args: &Cli,
+    api: &forge::ForgeApi,
+    rows: &[Vec<TableCell>],
+) -> io::Result<()> {
+    let header =
+        ["Module-Name", "Old", "New", "Change"].map(|text| TableCell::Text(text.to_string()));
+    let counts = format!("{} modules changed", rows.len());
+    let palette = Palette::new(args.color_scheme, &args.status_color);
+    let naming = ModuleNames {
+        style: args.name_style,
+        api,
+    };
+    print_details_start(out, args.format, args.md_style, &counts)?;
+    print_version_table(
+        out,
+        args.format,
+        args.md_style,
+        &naming,
+        &palette,
+        args.max_width,
+        &header,
+        rows,
+        None,
+    )?;
+    print_details_end(out, args.format, args.md_style)
+}
+
+/// Builds a terminal cell for a version, empty if there is none
+fn terminal_cell(palette: &Palette, cell: Option<&VersionCell>) -> Cell {
+    match cell {
+        Some(VersionCell { text, status }) => {
+            match status.and_then(|status| palette.color(status)) {
+                Some(color) => Cell::new(text).bg(color).fg(Color::Black),
+                None => Cell::new(text),
+            }
+        }
+        None => Cell::new(""),
+    }
+}
+
+/// Formats a version for the text-based formats, empty if there is none
+fn format_cell(format: OutputFormat, md_style: MdStyle, cell: Option<&VersionCell>) -> String {
+    match (format, cell) {
+        (OutputFormat::Jira, Some(cell)) => format!(
+            "{{{{{}}}}}{}",
+            cell.text,
+            cell.status.map(Status::jira_marker).unwrap_or_default()
+        ),
+        (OutputFormat::Md, Some(cell)) => format!(
+            "`{}`{}",
+            cell.text,
+            cell.status
+                .map(|status| status.md_marker(md_style))
+                .unwrap_or_default()
+        ),
+        (OutputFormat::Html, Some(cell)) => match cell.status {
+            Some(status) => format!(
+                "<td class=\"{}\">{}</td>",
+                status.css_class(),
+                html_escape(&cell.text)
+            ),
+            None => format!("<td>{}</td>", html_escape(&cell.text)),
+        },
+        (OutputFormat::Adoc, Some(cell)) => format!(
+            "`{}`{}",
+            cell.text,
+            cell.status.map(Status::adoc_marker).unwrap_or_default()
+        ),
+        (OutputFormat::Tsv | OutputFormat::Ndjson, Some(cell)) => cell.text.clone(),
+        (OutputFormat::Html, None) => "<td></td>".to_string(),
+        (OutputFormat::Tsv | OutputFormat::Ndjson, None) => String::new(),
+        (OutputFormat::TerminalTable, _) | (_, None) => " ".to_string(),
+    }
+}
+
+/// Links the module `name` to its page on the Forge's website, showing it in the given style. A
+/// module without such a page is not linked.
+fn module_link(format: OutputFormat, names: &ModuleNames, name: &str) -> String {
+    let title = &names.display(name);
+    let Some(url) = names.api.web_url(name) else {
+        return match format {
+            OutputFormat::Html => html_escape(title),
+            _ => title.to_string(),
+        };
+    };
+    match format {
+        OutputFormat::TerminalTable => forge_module_console_hyperlink(&url, title),
+        OutputFormat::Jira => format!("[{title}|{url}]"),
+        OutputFormat::Md => format!("[{title}]({url})"),
+        OutputFormat::Html => format!(
+            "<a href=\"{}\">{}</a>",
+            html_escape(&url),
+            html_escape(title)
+        ),
+        OutputFormat::Adoc => format!("{url}[{title}]"),
+        OutputFormat::Tsv | OutputFormat::Ndjson => title.to_string(),
+    }
+}
+
+/// Formats a cell of the branches table for the text-based formats. For HTML, this includes the
+/// `<td>` element.
+fn format_table_cell(
+    format: OutputFormat,
+    md_style: MdStyle,
+    names: &ModuleNames,
+    cell: &TableCell,
+) -> String {
+    match (format, cell) {
+        (_, TableCell::Version(version)) => format_cell(format, md_style, version.as_ref()),
+        (OutputFormat::Html, TableCell::Text(text)) => format!("<td>{}</td>", html_escape(text)),
+        (OutputFormat::Html, TableCell::Module(name)) => {
+            format!("<td>{}</td>", module_link(format, names, name))
+        }
+        (OutputFormat::Tsv | OutputFormat::Ndjson, TableCell::Text(text)) => text.clone(),
+        (_, TableCell::Text(text)) if text.is_empty() => " ".to_string(),
+        (_, TableCell::Text(text)) => text.clone(),
+        (_, TableCell::Module(name)) => module_link(format, names, name),
+    }
+}
+
+/// Prints the branches table. The optional `footer` is a row set apart from the others, like the
+/// totals.
+#[allow(clippy::too_many_arguments)]
+fn print_version_table(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    md_style: MdStyle,
+    names: &ModuleNames,
+    palette: &Palette,
+    max_width: Option<u16>,
+    header: &[TableCell],
+    rows: &[Vec<TableCell>],
+    footer: Option<&[TableCell]>,
+) -> io::Result<()> {
+    // headers are never linked in the terminal, they might get wrapped
+    let header_text: Vec<String> = header
+        .iter()
+        .map(|cell| match cell {
+            TableCell::Text(text) => text.clone(),
+            TableCell::Module(name) => names.display(name),
+            TableCell::Version(version) => {
+                version.as_ref().map(|v| v.text.clone()).unwrap_or_default()
+            }
+        })
+        .collect();
+    match format {
+        OutputFormat::TerminalTable => {
+            let mut table = terminal_table(header_text, max_width);
+            for row in rows {
+                table.add_row(row.iter().map(|cell| {
+                    match cell {
+                        TableCell::Text(text) => Cell::new(text),
+                        TableCell::Module(name) => Cell::new(module_link(format, names, name))
+                            .add_attribute(comfy_table::Attribute::Underlined),
+                        TableCell::Version(version) => terminal_cell(palette, version.as_ref()),
+                    }
+                }));
+            }
+            if let Some(footer) = footer {
+                table.add_row(footer.iter().map(|cell| {
+                    Cell::new(format_table_cell(format, md_style, names, cell).trim())
+                        .add_attribute(comfy_table::Attribute::Bold)
+                }));
+            }
+            writeln!(out, "{table}")?;
+        }
+        OutputFormat::Jira => {
+            let header: Vec<String> = header
+                .iter()
+                .zip(&header_text)
+                .map(|(cell, text)| match cell {
+                    TableCell::Module(name) => module_link(format, names, name),
+                    _ => format!("{{{{{text}}}}}"),
+                })
+                .collect();
+            writeln!(out, "||{}||", header.join("||"))?;
+            for row in rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "|{}|", cells.join("|"))?;
+            }
+            if let Some(footer) = footer {
+                let cells: Vec<String> = footer
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "||{}||", cells.join("||"))?;
+            }
+        }
+        OutputFormat::Md => {
+            let header: Vec<String> = header
+                .iter()
+                .zip(&header_text)
+                .map(|(cell, text)| match cell {
+                    TableCell::Module(name) => module_link(format, names, name),
+                    _ => text.clone(),
+                })
+                .collect();
+            writeln!(out, "|{}|", header.join("|"))?;
+            // create the simplest-possible header with alignment-specification:
+            writeln!(
+                out,
+                "|{}|",
+                (0..header.len())
+                    .map(|_| ": - ")
+                    .collect::<Vec<&str>>()
+                    .join("|")
+            )?;
+            for row in rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "|{}|", cells.join("|"))?;
+            }
+            if let Some(footer) = footer {
+                let cells: Vec<String> = footer
+                    .iter()
+                    .map(|c| match format_table_cell(format, md_style, names, c) {
+                        text if text.trim().is_empty() => text,
+                        text => format!("**{text}**"),
+                    })
+                    .collect();
+                writeln!(out, "|{}|", cells.join("|"))?;
+            }
+        }
+        OutputFormat::Html => {
+            let header: Vec<String> = header
+                .iter()
+                .zip(&header_text)
+                .map(|(cell, text)| match cell {
+                    TableCell::Module(name) => module_link(format, names, name),
+                    _ => html_escape(text),
+                })
+                .collect();
+            writeln!(out, "<table>")?;
+            writeln!(
+                out,
+                "<thead><tr><th>{}</th></tr></thead>",
+                header.join("</th><th>")
+            )?;
+            writeln!(out, "<tbody>")?;
+            for row in rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "<tr>{}</tr>", cells.join(""))?;
+            }
+            writeln!(out, "</tbody>")?;
+            if let Some(footer) = footer {
+                let cells: Vec<String> = footer
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "<tfoot><tr>{}</tr></tfoot>", cells.join(""))?;
+            }
+            writeln!(out, "</table>")?;
+        }
+        OutputFormat::Adoc => {
+            let header: Vec<String> = header
+                .iter()
+                .zip(&header_text)
+                .map(|(cell, text)| match cell {
+                    TableCell::Module(name) => module_link(format, names, name),
+                    _ => text.clone(),
+                })
+                .collect();
+            writeln!(
+                out,
+                "[cols=\"{}*\",options=\"{}\"]",
+                header.len(),
+                if footer.is_some() {
+                    "header,footer"
+                } else {
+                    "header"
+                }
+            )?;
+            writeln!(out, "|===")?;
+            writeln!(out, "|{}", header.join(" |"))?;
+            for row in rows.iter().map(Vec::as_slice).chain(footer) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "|{}", cells.join(" |"))?;
+            }
+            writeln!(out, "|===")?;
+        }
+        OutputFormat::Tsv => {
+            // the footer is just another line, there is no way to set it apart
+            writeln!(out, "{}", header_text.join("\t"))?;
+            for row in rows.iter().map(Vec::as_slice).chain(footer) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "{}", cells.join("\t"))?;
+            }
+        }
+        // the footer sums up the rows, which the consumer can do as well
+        OutputFormat::Ndjson => {
+            for row in rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|c| format_table_cell(format, md_style, names, c))
+                    .collect();
+                writeln!(out, "{}", json_object(&header_text, &cells))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Explains the colours and markers of `statuses` used by the branches view
+fn print_legend(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    md_style: MdStyle,
+    baseline: Baseline,
+    palette: &Palette,
+    statuses: &[Status],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::TerminalTable => {
+            let mut legend = Table::new();
+            legend.load_preset(comfy_table::presets::NOTHING);
+            for status in statuses {
+                let sample = match palette.color(*status) {
+                    Some(color) => Cell::new(" 1.0.0 ").bg(color).fg(Color::Black),
+                    None => Cell::new(" 1.0.0 "),
+                };
+                legend.add_row([sample, Cell::new(status.description(baseline))]);
+            }
+            writeln!(out, "{legend}")?;
+        }
+        OutputFormat::Jira => {
+            writeln!(out)?;
+            for status in statuses.iter().filter(|s| !s.jira_marker().is_empty()) {
+                writeln!(
+                    out,
+                    "*{}: {}",
+                    status.jira_marker(),
+                    status.description(baseline)
+                )?;
+            }
+        }
+        OutputFormat::Md => {
+            writeln!(out)?;
+            for status in statuses
+                .iter()
+                .filter(|s| !s.md_marker(md_style).is_empty())
+            {
+                writeln!(
+                    out,
+                    "-{}: {}",
+                    status.md_marker(md_style),
+                    status.description(baseline)
+                )?;
+            }
+        }
+        OutputFormat::Adoc => {
+            writeln!(out)?;
+            for status in statuses.iter().filter(|s| !s.adoc_marker().is_empty()) {
+                writeln!(
+                    out,
+                    "*{}: {}",
+                    status.adoc_marker(),
+                    status.description(baseline)
+                )?;
+            }
+        }
+        OutputFormat::Html => {
+            writeln!(out, "<ul class=\"legend\">")?;
+            for status in statuses {
+                writeln!(
+                    out,
+                    "<li class=\"{}\">{}</li>",
+                    status.css_class(),
+                    status.description(baseline)
+                )?;
+            }
+            writeln!(out, "</ul>")?;
+        }
+        // there are no colours or markers to explain
+        OutputFormat::Tsv | OutputFormat::Ndjson => (),
+    };
+    Ok(())
+}
+
+/// Prints a small table mapping each branch to its tip commit
+fn print_commit_info(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    prefix: &str,
+    branches: &[&models::BranchMeta],
+) -> io::Result<()> {
+    // the commits would be taken for more rows of the table
+    if format == OutputFormat::Ndjson {
+        return Ok(());
+    }
+    let header = ["Branch", "Commit", "Author", "Date"];
+    let rows: Vec<[String; 4]> = branches
+        .iter()
+        .map(|bm| {
+            [
+                short_branch_name(&bm.name, prefix).to_string(),
+                bm.oid.to_string()[..7].to_string(),
+                bm.author.clone(),
+                bm.commit_date.format("%Y-%m-%d %H:%M").to_string(),
+            ]
+        })
+        .collect();
+    if matches!(
+        format,
+        OutputFormat::Jira | OutputFormat::Md | OutputFormat::Adoc | OutputFormat::Tsv
+    ) {
+        // separate it from the table above
+        writeln!(out)?;
+    }
+    print_plain_table(out, format, "commit-info", &header, &rows)
+}
+
+/// Creates a table for the terminal whose columns are wrapped to fit into `max_width` columns, or
+/// the width of the terminal if not given. The first column holds the module names, which are
+/// never wrapped as that would break their hyperlinks.
+fn terminal_table<T: Into<comfy_table::Row>>(header: T, max_width: Option<u16>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header)
+        .set_constraints([comfy_table::ColumnConstraint::ContentWidth]);
+    if let Some(width) = max_width {
+        table.set_width(width);
+    }
+    table
+}
+
+/// Prints a table of plain text cells. For HTML, the table gets the CSS class `class`.
+fn print_plain_table<R: AsRef<[String]>>(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    class: &str,
+    header: &[&str],
+    rows: &[R],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::TerminalTable => {
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_header(header);
+            for row in rows {
+                table.add_row(row.as_ref());
+            }
+            writeln!(out, "{table}")?;
+        }
+        OutputFormat::Jira => {
+            writeln!(out, "||{}||", header.join("||"))?;
+            for row in rows {
+                writeln!(out, "|{}|", row.as_ref().join("|"))?;
+            }
+        }
+        OutputFormat::Md => {
+            writeln!(out, "|{}|", header.join("|"))?;
+            writeln!(out, "|{}|", vec![": - "; header.len()].join("|"))?;
+            for row in rows {
+                writeln!(out, "|{}|", row.as_ref().join("|"))?;
+            }
+        }
+        OutputFormat::Adoc => {
+            writeln!(out, "[cols=\"{}*\",options=\"header\"]", header.len())?;
+            writeln!(out, "|===")?;
+            writeln!(out, "|{}", header.join(" |"))?;
+            for row in rows {
+                writeln!(out, "|{}", row.as_ref().join(" |"))?;
+            }
+            writeln!(out, "|===")?;
+        }
+        OutputFormat::Html => {
+            writeln!(out, "<table class=\"{class}\">")?;
+            writeln!(
+                out,
+                "<thead><tr><th>{}</th></tr></thead>",
+                header.join("</th><th>")
+            )?;
+            writeln!(out, "<tbody>")?;
+            for row in rows {
+                let cells: Vec<String> =
+                    row.as_ref().iter().map(|cell| html_escape(cell)).collect();
+                writeln!(out, "<tr><td>{}</td></tr>", cells.join("</td><td>"))?;
+            }
+            writeln!(out, "</tbody>")?;
+            writeln!(out, "</table>")?;
+        }
+        OutputFormat::Tsv => {
+            writeln!(out, "{}", header.join("\t"))?;
+            for row in rows {
+                writeln!(out, "{}", row.as_ref().join("\t"))?;
+            }
+        }
+        OutputFormat::Ndjson => {
+            for row in rows {
+                writeln!(out, "{}", json_object(header, row.as_ref()))?;
+            }
+        }
+    };
+    Ok(())
+}
+
+/// A row as a JSON object, with the cells keyed by the names of their columns
+fn json_object<S: AsRef<str>>(header: &[S], cells: &[String]) -> serde_json::Value {
+    serde_json::Value::Object(
+        header
+            .iter()
+            .zip(cells)
+            .map(|(name, cell)| (name.as_ref().to_string(), cell.clone().into()))
+            .collect(),
+    )
+}
+
+/// Prints a note below the table if `--limit` cut off some of the `total` rows, `what` names them
+fn print_limit_footer(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    limit: Option<usize>,
+    total: usize,
+    what: &str,
+) -> io::Result<()> {
+    let Some(limit) = limit.filter(|limit| *limit < total) else {
+        return Ok(());
+    };
+    match format {
+        OutputFormat::Html => writeln!(out, "<p>showing {limit} of {total} {what}</p>")?,
+        // a line directly below the table would be considered part of it
+        OutputFormat::Jira | OutputFormat::Md | OutputFormat::Adoc => {
+            writeln!(out, "\nshowing {limit} of {total} {what}")?
+        }
+        OutputFormat::TerminalTable => writeln!(out, "showing {limit} of {total} {what}")?,
+        // any line would be taken for a row
+        OutputFormat::Tsv | OutputFormat::Ndjson => (),
+    };
+    Ok(())
+}
+
+/// Prints `text` above one of several tables, e.g. the pages of `--page-branches`
+fn print_page_heading(out: &mut dyn Write, format: OutputFormat, text: &str) -> io::Result<()> {
+    match format {
+        OutputFormat::Html => writeln!(out, "<p>{text}</p>")?,
+        // the table would be taken for a part of a line directly above it
+        OutputFormat::Jira | OutputFormat::Md | OutputFormat::Adoc => writeln!(out, "\n{text}\n")?,
+        OutputFormat::TerminalTable => writeln!(out, "{text}")?,
+        // `--page-branches` is rejected for them, any line would be taken for a row
+        OutputFormat::Tsv | OutputFormat::Ndjson => (),
+    };
+    Ok(())
+}
+
+/// Opens the collapsible section of `--md-style github`, showing `counts` while it is collapsed.
+/// Does nothing for other formats and styles.
+fn print_details_start(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    md_style: MdStyle,
+    counts: &str,
+) -> io::Result<()> {
+    if format == OutputFormat::Md && md_style == MdStyle::Github {
+        // GitHub only renders Markdown inside the section if it is set apart by a blank line
+        writeln!(out, "<details><summary>{}</summary>\n", html_escape(counts))?;
+    }
+    Ok(())
+}
+
+/// Closes the section opened by `print_details_start`
+fn print_details_end(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    md_style: MdStyle,
+) -> io::Result<()> {
+    if format == OutputFormat::Md && md_style == MdStyle::Github {
+        writeln!(out, "\n</details>")?;
+    }
+    Ok(())
+}
+
+/// Escapes `text` for a quoted string in DOT
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape the characters that have a special meaning in HTML text and attribute values
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn forge_module_console_hyperlink(url: &str, title: &str) -> String {
+    format!("\x1B]8;;{url}\x1B\\{title}\x1B]8;;\x1B\\",)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_colors() {
+        assert_eq!(
+            parse_status_color("deprecated=dark-red"),
+            Ok((Status::Deprecated, Some(Color::DarkRed)))
+        );
+        assert_eq!(
+            parse_status_color("Behind=#ff8000"),
+            Ok((
+                Status::Behind,
+                Some(Color::Rgb {
+                    r: 255,
+                    g: 128,
+                    b: 0
+                })
+            ))
+        );
+        // turns the colour of a status off
+        assert_eq!(
+            parse_status_color("current=none"),
+            Ok((Status::Current, None))
+        );
+        assert_eq!(
+            parse_status_color("current=#zzzzzz"),
+            Err("'#zzzzzz' is not a valid colour".to_string())
+        );
+        // seven bytes, but not seven characters
+        assert_eq!(
+            parse_status_color("current=#äöü"),
+            Err("'#äöü' is not a valid colour".to_string())
+        );
+        assert!(
+            parse_status_color("nope=red").is_err_and(|e| e.starts_with("unknown status 'nope'"))
+        );
+        assert!(parse_status_color("red").is_err());
+    }
+
+    #[test]
+    fn max_behind() {
+        assert_eq!(
+            parse_max_behind("minor:2"),
+            Ok(models::VersionLag {
+                major: 0,
+                minor: 2,
+                patch: 0
+            })
+        );
+        assert_eq!(
+            parse_max_behind("minor"),
+            Err("expected <major|minor|patch>:<N>, e.g. minor:2".to_string())
+        );
+        assert!(
+            parse_max_behind("minor:two").is_err_and(|e| e.starts_with("'two' is not a number"))
+        );
+        assert_eq!(
+            parse_max_behind("build:2"),
+            Err("unknown version component 'build'".to_string())
+        );
+    }
+
+    #[test]
+    fn module_patterns() {
+        let matches = |pattern: &str, name: &str| module_pattern(pattern).unwrap().is_match(name);
+        assert!(matches("puppetlabs/*", "puppetlabs-stdlib"));
+        assert!(matches("puppetlabs-*", "puppetlabs-stdlib"));
+        assert!(!matches("puppetlabs/*", "puppet-systemd"));
+        // globs match the whole name
+        assert!(!matches("stdlib", "puppetlabs-stdlib"));
+        assert!(matches("puppet-?ystemd", "puppet-systemd"));
+        assert!(!matches("puppet-?systemd", "puppet-systemd"));
+        // only `*` and `?` are special in a glob
+        assert!(matches("a.b+c", "a.b+c"));
+        assert!(!matches("a.b+c", "axbbc"));
+        assert!(matches("/^puppet(labs)?-/", "puppetlabs-stdlib"));
+        // `//` is not an (empty) regex but a glob
+        assert!(matches("//", "--"));
+        assert!(!matches("//", "puppetlabs-stdlib"));
+        assert!(module_pattern("/(/").is_err());
+    }
+
+    #[test]
+    fn remote_urls() {
+        assert!(is_remote_url("https://git.example.com/control.git"));
+        assert!(is_remote_url("ssh://git@git.example.com/control.git"));
+        assert!(is_remote_url("file:///srv/git/control.git"));
+        assert!(is_remote_url("git@git.example.com:puppet/control.git"));
+        assert!(!is_remote_url("/srv/git/control"));
+        assert!(!is_remote_url("."));
+        // local paths may contain `:` and `@` as well
+        assert!(!is_remote_url("/srv/git/control:old"));
+        assert!(!is_remote_url("/home/jane@example.com/control"));
+        assert!(!is_remote_url("backup@2024/control"));
+    }
+
+    #[test]
+    fn transposed_branch_table() {
+        let args = Cli::parse_from(["puppetstuff", "--transpose", "forge-branches"]);
+        let api = forge::ForgeApi::new(None, "https://forge.example.com", None);
+        let naming = ModuleNames {
+            style: args.name_style,
+            api: &api,
+        };
+        let palette = Palette::new(args.color_scheme, &args.status_color);
+        let version = |text: &str| {
+            TableCell::Version(Some(VersionCell {
+                text: text.to_string(),
+                status: None,
+            }))
+        };
+        // four modules, but only the Forge and a single branch
+        let modules = ["puppet-a", "puppet-b", "puppet-c", "puppet-d"];
+        let mut header = vec![TableCell::Text("Branch".to_string())];
+        header.extend(modules.map(|name| TableCell::Module(name.to_string())));
+        let rows: Vec<Vec<TableCell>> = ["Forge latest", "dev (4)"]
+            .into_iter()
+            .map(|label| {
+                let mut row = vec![TableCell::Text(label.to_string())];
+                row.extend(modules.map(|_| version("1.0.0")));
+                row
+            })
+            .collect();
+        let mut out = vec![];
+        print_branch_table(
+            &mut out,
+            &args,
+            &naming,
+            &palette,
+            &[0, 1, 2],
+            &header,
+            &rows,
+            None,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(modules
+            .iter()
+            .all(|name| out.contains(&naming.display(name))));
+        assert!(out.contains("dev (4)"));
+    }
 }
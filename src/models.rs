@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use chrono::{DateTime, Utc};
+use git2::Oid;
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Specifies how to select the revision that is used by the puppet master
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GitRef {
     /// No specific version, use newest commit in default branch. The commit hash cannot be
     /// determined without contacting the remote repository.
@@ -15,23 +19,73 @@ pub enum GitRef {
     Tag(String),
     /// The newest commit in the branch (exact commit depends on the time of check).
     Branch(String),
+    /// A tag, branch or commit hash (`:ref`), which one is only known to the remote repository.
+    Ref(String),
+    /// The branch named like the environment (`:branch => :control_branch`), so the module
+    /// follows the control repository's branches (r10k-specific).
+    ControlBranch,
+}
+
+impl GitRef {
+    /// Returns the version a tag stands for if it is valid semver, like `1.4.2`. The common
+    /// leading `v` (`v1.4.2`) is allowed, unlike for Forge versions.
+    pub fn version(&self) -> Option<Version> {
+        match self {
+            GitRef::Tag(tag) => {
+                let tag = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+                Version::parse(tag).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GitRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitRef::Head => write!(f, "HEAD"),
+            GitRef::Commit(commit) => write!(f, "commit {commit}"),
+            GitRef::Tag(tag) => write!(f, "tag {tag}"),
+            GitRef::Branch(branch) => write!(f, "branch {branch}"),
+            GitRef::Ref(reference) => write!(f, "ref {reference}"),
+            GitRef::ControlBranch => write!(f, "control branch"),
+        }
+    }
+}
+
+/// Boolean attributes of a git module that only change how g10k fetches or installs it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitFlags {
+    /// Don't install the module's `spec` directory (`:exclude_spec`)
+    pub exclude_spec: bool,
+    /// Go on if the repository can't be reached (`:ignore_unreachable`)
+    pub ignore_unreachable: bool,
+    /// Authenticate through the ssh-agent (`:use_ssh_agent`)
+    pub use_ssh_agent: bool,
+    /// Leave the module's directory alone, it is managed by hand (`:local`)
+    pub local: bool,
 }
 
 /// Specification where to look for a module in a git repository and how it is handled by g10k.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GitSpec {
     /// URL to the repository
     pub url: Option<String>,
     /// Git reference, such as a tag or branch name
     pub reference: GitRef,
-    /// Fallback-branch if `reference` is a branch
+    /// Fallback-branch if `reference` is a branch (`:fallback`, or `:default_branch` for r10k)
     pub fallback: Option<String>,
     /// If branches should be linked (r10k-specific)
     pub link: bool,
+    /// Directory the module is installed to instead of the `moduledir` (`:install_path`)
+    pub install_path: Option<String>,
+    /// g10k's boolean attributes like `:exclude_spec`
+    #[serde(default)]
+    pub flags: GitFlags,
 }
 
 /// A module specification from a `Puppetfile`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Module {
     /// Forge module (name and version)
     Forge(String, Version),
@@ -39,12 +93,184 @@ pub enum Module {
     Git(String, GitSpec),
 }
 
+/// Directory the modules are installed to if the `Puppetfile` does not set a `moduledir`
+pub const DEFAULT_MODULEDIR: &str = "modules";
+
+/// A branch and the modules found in its `Puppetfile`
 #[derive(Debug, Clone)]
 pub struct BranchMeta {
     pub name: String,
-    // pub oid: Oid,
-    // pub author_date: Time,
-    // pub commit_date: Time,
-    // pub author: String,
+    /// The branch's tip commit
+    pub oid: Oid,
+    /// Author date of the branch's tip
+    pub author_date: DateTime<Utc>,
+    /// Commit date of the branch's tip
+    pub commit_date: DateTime<Utc>,
+    /// Author of the branch's tip as `name <email>`
+    pub author: String,
+    /// Content of the branch's `Puppetfile`
+    pub puppetfile: String,
+    /// Directory the modules are installed to, relative to the environment
+    pub moduledir: String,
     pub modules: Vec<Module>,
 }
+
+impl BranchMeta {
+    /// Returns the directory `module` is deployed to, relative to the environment, e.g.
+    /// `modules/stdlib` for `puppetlabs-stdlib` or `site/profile` for a git module with
+    /// `:install_path => 'site'`.
+    pub fn install_path(&self, module: &Module) -> String {
+        match module {
+            // the directory is named after the module, without its author
+            Module::Forge(name, _) => {
+                let short_name = name.split_once('-').map_or(name.as_str(), |(_, n)| n);
+                format!("{}/{short_name}", self.moduledir)
+            }
+            Module::Git(name, spec) => format!(
+                "{}/{name}",
+                spec.install_path.as_deref().unwrap_or(&self.moduledir)
+            ),
+        }
+    }
+}
+
+/// Groups the branches that use exactly the same modules, pinned the same way, regardless of their
+/// order in the `Puppetfile`. A git module following `:control_branch` deploys another branch of
+/// the module in each of them, so it keeps them apart. Only groups of several branches are
+/// returned, in the order of `branches`.
+pub fn identical_branches<'a>(branches: &[&'a BranchMeta]) -> Vec<Vec<&'a BranchMeta>> {
+    let mut groups: Vec<Vec<&BranchMeta>> = vec![];
+    // the sorted pins of a branch → its group
+    let mut group_of: HashMap<Vec<(&str, String)>, usize> = HashMap::new();
+    for branch in branches {
+        let mut pins: Vec<(&str, String)> = branch
+            .modules
+            .iter()
+            .map(|module| match module {
+                Module::Forge(name, version) => (name.as_str(), version.to_string()),
+                Module::Git(name, spec) => {
+                    let reference = match spec.reference {
+                        GitRef::ControlBranch => format!("branch {}", branch.name),
+                        ref reference => reference.to_string(),
+                    };
+                    let url = spec.url.as_deref().unwrap_or_default();
+                    (name.as_str(), format!("{url} {reference}"))
+                }
+            })
+            .collect();
+        pins.sort();
+        let idx = *group_of.entry(pins).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[idx].push(branch);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// How far one version is behind another, counted in the most significant component that differs
+/// (`8.5.0` → `9.1.0` is one major release behind). Ordering is lexicographic, so a single major
+/// release outweighs any number of minor releases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionLag {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl VersionLag {
+    /// Computes how far `current` is behind `target`. If `current` is not older, the lag is zero.
+    /// Build metadata is ignored, `1.2.0+deploy5` is not behind `1.2.0`.
+    pub fn between(current: &Version, target: &Version) -> Self {
+        if current.cmp_precedence(target).is_ge() {
+            return Self::default();
+        }
+        if current.major != target.major {
+            Self {
+                major: target.major - current.major,
+                minor: 0,
+                patch: 0,
+            }
+        } else if current.minor != target.minor {
+            Self {
+                major: 0,
+                minor: target.minor - current.minor,
+                patch: 0,
+            }
+        } else {
+            Self {
+                major: 0,
+                minor: 0,
+                patch: target.patch.saturating_sub(current.patch),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branch(name: &str, modules: Vec<Module>) -> BranchMeta {
+        BranchMeta {
+            name: name.to_string(),
+            oid: Oid::zero(),
+            author_date: DateTime::default(),
+            commit_date: DateTime::default(),
+            author: String::new(),
+            puppetfile: String::new(),
+            moduledir: DEFAULT_MODULEDIR.to_string(),
+            modules,
+        }
+    }
+
+    fn git(name: &str, reference: GitRef) -> Module {
+        Module::Git(
+            name.to_string(),
+            GitSpec {
+                url: Some(format!("https://git.example.com/{name}.git")),
+                reference,
+                fallback: None,
+                link: false,
+                install_path: None,
+                flags: GitFlags::default(),
+            },
+        )
+    }
+
+    #[test]
+    fn identical() {
+        let stdlib = Module::Forge("puppetlabs-stdlib".to_string(), Version::new(9, 7, 0));
+        let systemd = Module::Forge("puppet-systemd".to_string(), Version::new(8, 1, 0));
+        let profile = git("profile", GitRef::Branch("main".to_string()));
+        let hiera = git("hieradata", GitRef::ControlBranch);
+        let branches = [
+            branch("origin/dev", vec![stdlib.clone(), profile.clone()]),
+            // same modules in another order
+            branch("origin/live", vec![profile.clone(), stdlib.clone()]),
+            branch("origin/qa", vec![stdlib.clone(), systemd.clone()]),
+            branch("origin/staging", vec![stdlib.clone(), profile.clone()]),
+            // each deploys its own branch of `hieradata`
+            branch("origin/test1", vec![stdlib.clone(), hiera.clone()]),
+            branch("origin/test2", vec![stdlib.clone(), hiera.clone()]),
+        ];
+        let branches: Vec<&BranchMeta> = branches.iter().collect();
+        let groups: Vec<Vec<&str>> = identical_branches(&branches)
+            .into_iter()
+            .map(|group| group.iter().map(|branch| branch.name.as_str()).collect())
+            .collect();
+        assert_eq!(groups, [["origin/dev", "origin/live", "origin/staging"]]);
+    }
+
+    #[test]
+    fn tag_versions() {
+        let tag = |tag: &str| GitRef::Tag(tag.to_string()).version();
+        assert_eq!(tag("1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag("v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag("V1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag("release-1.2.3"), None);
+        assert_eq!(tag("vv1.2.3"), None);
+        assert_eq!(GitRef::Branch("1.2.3".to_string()).version(), None);
+    }
+}
@@ -2,46 +2,314 @@
 
 use regex::Regex;
 use semver::Version;
-use std::sync::LazyLock;
-use tracing::{debug, error, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, trace, warn};
 
 use crate::models::*;
 
+/// Something in a `Puppetfile` that could not be handled, along with the (1-based) line number.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParseWarning {
+    /// A forge module whose version is not valid semver. The module is skipped.
+    InvalidVersion {
+        line: usize,
+        name: String,
+        version: String,
+    },
+    /// A forge module that is not pinned to a version (`:latest` or no version at all). The
+    /// module is skipped as there is nothing to compare.
+    Unpinned { line: usize, name: String },
+    /// A git attribute that is not known to the parser.
+    UnknownGitAttribute { line: usize, name: String },
+    /// A git attribute line that does not belong to a git module. The line is skipped.
+    AttributeOutsideModule { line: usize },
+    /// A line starting with `mod` that matches none of the known forms. The module is skipped.
+    UnmatchedModule { line: usize, text: String },
+    /// A line pulling in Ruby code from elsewhere (`require`, `eval` and the like), whose modules
+    /// are not known.
+    ExternalContent { line: usize, text: String },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::InvalidVersion {
+                line,
+                name,
+                version,
+            } => write!(
+                f,
+                "line {line}: version '{version}' of module {name} is not valid semver, skipping"
+            ),
+            ParseWarning::Unpinned { line, name } => write!(
+                f,
+                "line {line}: module {name} is not pinned to a version, skipping"
+            ),
+            ParseWarning::UnknownGitAttribute { line, name } => {
+                write!(f, "line {line}: unknown git attribute '{name}'")
+            }
+            ParseWarning::AttributeOutsideModule { line } => write!(
+                f,
+                "line {line}: git attribute outside of a git module, skipping"
+            ),
+            ParseWarning::UnmatchedModule { line, text } => write!(
+                f,
+                "line {line}: '{text}' looks like a module but could not be parsed, skipping"
+            ),
+            ParseWarning::ExternalContent { line, text } => write!(
+                f,
+                "line {line}: '{text}' loads content from elsewhere, the modules declared there are missing"
+            ),
+        }
+    }
+}
+
+/// The result of parsing a `Puppetfile`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ParsedPuppetfile {
+    /// Modules in the order they were declared
+    pub modules: Vec<Module>,
+    /// Directory the modules are installed to, if set with `moduledir`
+    pub moduledir: Option<String>,
+    /// Problems encountered while parsing
+    pub warnings: Vec<ParseWarning>,
+    /// Where the versions of the forge modules are written, in the order of the modules
+    pub pins: Vec<VersionPin>,
+}
+
+/// Where the version of a forge module is written in a `Puppetfile`, so it can be replaced by
+/// [`bump_forge_modules`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionPin {
+    /// Name of the module, normalized to `author-module`
+    pub name: String,
+    /// 1-based line number
+    pub line: usize,
+    /// Byte range of the version (without the quotes) in the `Puppetfile`
+    pub span: Range<usize>,
+}
+
+/// Matches a normal forge line like `mod "puppet/dance", "1.0.0"`
+static FORGE_MODULE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^\s*(?:mod)\s+['\"](?P<name>[^'\"]+[-/][^'\"]+)['\"]\s*,\s*['\"](?P<version>.*)['\"]"#,
+    )
+    .unwrap()
+});
+
+/// A module whose attributes are still being read. Whether it is a forge or a git module is only
+/// known once it is complete: `:version` makes it a forge module, `:git` a git module.
+struct PendingModule {
+    name: String,
+    /// Line of the `mod` keyword
+    line: usize,
+    spec: GitSpec,
+    /// Value of `:version`
+    version: Option<String>,
+    /// Line and byte range of the value of `:version` in the `Puppetfile`, see [`VersionPin`]
+    version_pin: Option<(usize, Range<usize>)>,
+}
+
+impl PendingModule {
+    /// Adds the complete module to `parsed`
+    fn finish(self, parsed: &mut ParsedPuppetfile) {
+        let Some(version) = self.version else {
+            parsed.modules.push(Module::Git(self.name, self.spec));
+            return;
+        };
+        if self.spec.url.is_some() {
+            debug!("Git module {} has a version, ignoring it", self.name);
+            parsed.warnings.push(ParseWarning::UnknownGitAttribute {
+                line: self.line,
+                name: "version".to_string(),
+            });
+            parsed.modules.push(Module::Git(self.name, self.spec));
+            return;
+        }
+        // same as the positional form, `mod 'puppetlabs/stdlib', '9.0.0'`
+        let name = self.name.replace("/", "-");
+        if version.trim() == ":latest" {
+            debug!("Unpinned forge module: {name}");
+            parsed.warnings.push(ParseWarning::Unpinned {
+                line: self.line,
+                name,
+            });
+            return;
+        }
+        match parse_forge_version(&version) {
+            Ok(version) => {
+                debug!("Forge module: {} {}", name, version);
+                if let Some((line, span)) = self.version_pin {
+                    parsed.pins.push(VersionPin {
+                        name: name.clone(),
+                        line,
+                        span,
+                    });
+                }
+                parsed.modules.push(Module::Forge(name, version));
+            }
+            Err(e) => {
+                debug!("Forge module {name} has invalid version {version}: {e}");
+                parsed.warnings.push(ParseWarning::InvalidVersion {
+                    line: self.line,
+                    name,
+                    version,
+                });
+            }
+        }
+    }
+}
+
+/// Parses the version of a forge module. Whitespace is dropped first, a stray space inside the
+/// quotes (`'9.0.0 '`) is easily made and hard to spot.
+fn parse_forge_version(version: &str) -> Result<Version, semver::Error> {
+    Version::parse(&version.split_whitespace().collect::<String>())
+}
+
+/// A forge module whose version was changed by [`bump_forge_modules`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bump {
+    /// 1-based line number
+    pub line: usize,
+    /// Name of the module, normalized to `author-module`
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    /// The line before and after the change
+    pub old_line: String,
+    pub new_line: String,
+}
+
+/// The result of [`bump_forge_modules`].
+#[derive(Debug, Default)]
+pub struct BumpedPuppetfile {
+    /// The updated `Puppetfile`
+    pub content: String,
+    /// The changes made, in the order of the lines
+    pub bumps: Vec<Bump>,
+}
+
+/// Updates the versions of the forge modules in a `Puppetfile` to the ones `target` returns for
+/// them. Only the version strings are touched, everything else (git modules, comments, quoting
+/// and formatting) is kept as it is. The modules are the ones [`parse_puppetfile`] finds, so
+/// disabled ones (e.g. in `=begin`/`=end`) are left alone. Modules for which `target` returns
+/// `None` or a version that is not newer are left alone as well.
+pub fn bump_forge_modules(
+    content: &str,
+    target: impl Fn(&str, &Version) -> Option<Version>,
+) -> BumpedPuppetfile {
+    let mut bumped = BumpedPuppetfile::default();
+    // the end of the part of `content` that has been copied to the result
+    let mut copied = 0;
+    for pin in parse_puppetfile(content).pins {
+        let Ok(from) = parse_forge_version(&content[pin.span.clone()]) else {
+            continue;
+        };
+        let Some(to) = target(&pin.name, &from).filter(|to| to.cmp_precedence(&from).is_gt())
+        else {
+            continue;
+        };
+        let line_start = content[..pin.span.start]
+            .rfind('\n')
+            .map_or(0, |idx| idx + 1);
+        let line_end = content[pin.span.end..]
+            .find('\n')
+            .map_or(content.len(), |idx| pin.span.end + idx);
+        bumped.bumps.push(Bump {
+            line: pin.line,
+            name: pin.name,
+            from,
+            to: to.clone(),
+            old_line: content[line_start..line_end].to_string(),
+            new_line: format!(
+                "{}{to}{}",
+                &content[line_start..pin.span.start],
+                &content[pin.span.end..line_end]
+            ),
+        });
+        bumped.content.push_str(&content[copied..pin.span.start]);
+        bumped.content.push_str(&to.to_string());
+        copied = pin.span.end;
+    }
+    bumped.content.push_str(&content[copied..]);
+    bumped
+}
+
 /// Parse a `Puppetfile` content into a list of modules, assuming it is compliant with `g10k`.
-pub fn parse_puppetfile(content: &str) -> Vec<Module> {
-    // Matches a normal forge line like `mod "puppet/dance", "1.0.0"`
-    static FORGE_MODULE_RE: LazyLock<Regex> = LazyLock::new(|| {
-        Regex::new(
-            r#"^\s*(?:mod)\s+['\"](?P<name>[^'\"]+[-/][^'\"]+)['\"],\s+['\"](?P<version>.*)['\"]"#,
-        )
-        .unwrap()
+///
+/// Parsing never panics. Lines that can't be handled are reported in
+/// [`ParsedPuppetfile::warnings`].
+pub fn parse_puppetfile(content: &str) -> ParsedPuppetfile {
+    // Matches an unpinned forge line like `mod "puppet/dance", :latest` or `mod "puppet/dance"`
+    static FORGE_UNPINNED_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"^\s*(?:mod)\s+['\"](?P<name>[^'\"]+[-/][^'\"]+)['\"]\s*(?:,\s*:latest\s*)?$"#)
+            .unwrap()
     });
-    // Matches a line like `mod "mymodule",`
-    static GIT_MODULE_RE: LazyLock<Regex> =
-        LazyLock::new(|| Regex::new(r#"^\s*(?:mod)\s+['\"](?P<name>[^'\"]+)['\"]\s*,$"#).unwrap());
-    static GIT_ATTRIBUTE_RE: LazyLock<Regex> = LazyLock::new(|| {
-        Regex::new(r#"^\s*:(?P<name>git|commit|tag|branch|ref|link|fallback)\s*=>\s*['\"]?(?P<value>[^'\"]+)['\"']?$"#).unwrap()
+    // Matches a line like `mod "mymodule",`, optionally followed by attributes on the same line
+    static GIT_MODULE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"^\s*(?:mod)\s+['\"](?P<name>[^'\"]+)['\"]\s*,\s*(?P<attributes>:.*)?$"#)
+            .unwrap()
     });
+    // Matches a line like `moduledir 'site'`
+    static MODULEDIR_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"^\s*moduledir\s+['\"](?P<dir>[^'\"]+)['\"]"#).unwrap());
+    // Matches any line starting with the `mod` keyword, for the ones matching none of the above
+    static MOD_KEYWORD_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^mod\b").unwrap());
+    // Matches Ruby that loads other files, like `eval(File.read('modules.rb'))`
+    static EXTERNAL_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?:require|require_relative|load)\b|\b(?:instance_)?eval\b").unwrap()
+    });
+    // Matches a line starting with an attribute like `:git => "https://…"`
+    static ATTRIBUTE_LINE_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"^\s*:(?P<name>\w+)\s*=>"#).unwrap());
+
+    // some editors start the file with a byte order mark, which `trim` leaves alone and which would
+    // hide the first line (a module or a `#!` line) from the patterns
+    let stripped = content.strip_prefix('\u{feff}').unwrap_or(content);
+    // where the current line starts in `content`, the versions are located in it including the
+    // byte order mark
+    let mut line_start = content.len() - stripped.len();
 
-    let mut modules: Vec<Module> = vec![];
-    let mut current_module = None;
-    for line in content
-        .split("\n")
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty() && !l.starts_with("#"))
-    {
+    let mut parsed = ParsedPuppetfile::default();
+    let mut current_module: Option<PendingModule> = None;
+    let mut in_block_comment = false;
+    for (idx, raw_line) in stripped.split('\n').enumerate() {
+        let line_no = idx + 1;
+        // where the trimmed line starts in `content`
+        let offset = line_start + raw_line.len() - raw_line.trim_start().len();
+        line_start += raw_line.len() + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Ruby block comments, used to disable a bunch of modules at once
+        if in_block_comment {
+            if line.starts_with("=end") {
+                in_block_comment = false;
+            }
+            continue;
+        }
+        if line.starts_with("=begin") {
+            in_block_comment = true;
+            continue;
+        }
         // q&d: get everything before the # symbol (breaks if used in URLs, but oh well)
         let line = match line.split_once('#') {
             Some((l, _)) => l.trim(),
             None => line, // line did not contain a comment
         };
-        trace!("{line}");
+        trace!("{line_no}: {line}");
 
         if let Some(caps) = FORGE_MODULE_RE.captures(line) {
-            if let Some(c_m) = current_module {
+            if let Some(c_m) = current_module.take() {
                 debug!("Forge module found → previously worked-on module is complete");
-                modules.push(c_m);
-                current_module = None;
+                c_m.finish(&mut parsed);
             }
             let name = caps
                 .name("name")
@@ -50,77 +318,889 @@ pub fn parse_puppetfile(content: &str) -> Vec<Module> {
                 .to_string()
                 // forge modules are "{author}-{name}" but r10k/g10k accepts a "{author}/{name}" as well. Canonicalize it here:
                 .replace("/", "-");
-            let version = Version::parse(&caps.name("version").unwrap().as_str()).unwrap();
-            debug!("Forge module: {} {}", name, version);
-            modules.push(Module::Forge(name, version));
+            let version_match = caps.name("version").unwrap();
+            let version = version_match.as_str();
+            match parse_forge_version(version) {
+                Ok(version) => {
+                    debug!("Forge module: {} {}", name, version);
+                    parsed.pins.push(VersionPin {
+                        name: name.clone(),
+                        line: line_no,
+                        span: offset + version_match.start()..offset + version_match.end(),
+                    });
+                    parsed.modules.push(Module::Forge(name, version));
+                }
+                Err(e) => {
+                    debug!("Forge module {name} has invalid version {version}: {e}");
+                    parsed.warnings.push(ParseWarning::InvalidVersion {
+                        line: line_no,
+                        name,
+                        version: version.to_string(),
+                    });
+                }
+            }
+        } else if let Some(caps) = FORGE_UNPINNED_RE.captures(line) {
+            if let Some(c_m) = current_module.take() {
+                debug!("Forge module found → previously worked-on module is complete");
+                c_m.finish(&mut parsed);
+            }
+            let name = caps.name("name").unwrap().as_str().replace("/", "-");
+            debug!("Unpinned forge module: {name}");
+            parsed.warnings.push(ParseWarning::Unpinned {
+                line: line_no,
+                name,
+            });
         } else if let Some(caps) = GIT_MODULE_RE.captures(line) {
-            if let Some(c_m) = current_module {
+            if let Some(c_m) = current_module.take() {
                 debug!("Git module found → previously worked-on module is complete");
-                modules.push(c_m.clone());
+                c_m.finish(&mut parsed);
             }
-            debug!("Git module: {}", &caps[1]);
-            current_module = Some(Module::Git(
-                caps.name("name").unwrap().as_str().to_string(),
-                GitSpec {
+            let name = caps.name("name").unwrap().as_str().to_string();
+            debug!("Git module: {name}");
+            let mut module = PendingModule {
+                name,
+                line: line_no,
+                spec: GitSpec {
                     url: None,
                     reference: GitRef::Head,
                     fallback: None,
                     link: false,
+                    install_path: None,
+                    flags: GitFlags::default(),
                 },
-            ));
-        } else if let Some(caps) = GIT_ATTRIBUTE_RE.captures(line) {
-            if let Some(ref mut c_m) = current_module {
-                let name = caps.name("name").unwrap().as_str();
-                let value = caps.name("value").unwrap().as_str();
-                debug!("Git attribute {name}");
-                match c_m {
-                    Module::Forge(_, _) => {
-                        error!("Git attribute but parsing forge mod");
-                        break;
-                    }
-                    Module::Git(_, spec) => {
-                        match name {
-                            "git" => {
-                                debug!("Found url: {value}");
-                                spec.url = Some(value.to_string());
-                            }
-                            "tag" => {
-                                debug!("Found tag: {value}");
-                                spec.reference = GitRef::Tag(value.to_string());
-                            }
-                            "branch" => {
-                                debug!("Found branch: {value}");
-                                spec.reference = GitRef::Branch(value.to_string());
-                            }
-                            "commit" => {
-                                debug!("Found commit hash: {value}");
-                                spec.reference = GitRef::Commit(value.to_string());
-                            }
-                            "fallback" => {
-                                debug!("Found fallback branch name: {value}");
-                                spec.fallback = Some(value.to_string());
-                            }
-                            "link" => {
-                                debug!("Found link setting");
-                                spec.link = true;
-                            }
-                            other => {
-                                warn!("Found unknown git attribute: {other} => {value}");
-                                break;
-                            }
-                        };
-                    }
-                };
-            } else {
-                error!("Hit a git attribute line but not parsing a module!");
-                break;
+                version: None,
+                version_pin: None,
+            };
+            if let Some(attributes) = caps.name("attributes") {
+                apply_git_attributes(
+                    &mut module,
+                    attributes.as_str(),
+                    offset + attributes.start(),
+                    line_no,
+                    &mut parsed,
+                );
+            }
+            current_module = Some(module);
+        } else if let Some(caps) = MODULEDIR_RE.captures(line) {
+            debug!("Module directory: {}", &caps["dir"]);
+            parsed.moduledir = Some(caps["dir"].to_string());
+        } else if let Some(caps) = ATTRIBUTE_LINE_RE.captures(line) {
+            match current_module {
+                Some(ref mut module) => {
+                    apply_git_attributes(module, line, offset, line_no, &mut parsed);
+                }
+                None => {
+                    debug!(
+                        "Hit attribute {} but not parsing a git module",
+                        &caps["name"]
+                    );
+                    parsed
+                        .warnings
+                        .push(ParseWarning::AttributeOutsideModule { line: line_no });
+                }
             }
+        } else if EXTERNAL_RE.is_match(line) {
+            // the module list would be incomplete without anyone noticing
+            debug!("External content: {line}");
+            parsed.warnings.push(ParseWarning::ExternalContent {
+                line: line_no,
+                text: line.to_string(),
+            });
+        } else if MOD_KEYWORD_RE.is_match(line) {
+            // a typo would make the module vanish silently otherwise
+            debug!("Unmatched module line: {line}");
+            parsed.warnings.push(ParseWarning::UnmatchedModule {
+                line: line_no,
+                text: line.to_string(),
+            });
         }
     }
     if let Some(c_m) = current_module {
         debug!("End of file → previously worked-on module is complete");
-        modules.push(c_m);
+        c_m.finish(&mut parsed);
+    }
+
+    parsed
+}
+
+/// Applies all the git attributes found in `attributes`, which starts at byte `offset` of the
+/// `Puppetfile`, to `module`. Unknown attributes are recorded as warnings.
+fn apply_git_attributes(
+    module: &mut PendingModule,
+    attributes: &str,
+    offset: usize,
+    line_no: usize,
+    parsed: &mut ParsedPuppetfile,
+) {
+    // Matches the name of an attribute like `:git =>`
+    static ATTRIBUTE_NAME_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#":(?P<name>\w+)\s*=>"#).unwrap());
+    // Matches the value following the `=>`, either quoted or a bare word like `true`
+    static ATTRIBUTE_VALUE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"^\s*(?:['\"](?P<quoted>[^'\"]*)['\"]|(?P<bare>[^\s,'\"]+))"#).unwrap()
+    });
+
+    let spec = &mut module.spec;
+    for caps in ATTRIBUTE_NAME_RE.captures_iter(attributes) {
+        let name = caps.name("name").unwrap().as_str();
+        let rest_start = caps.get(0).unwrap().end();
+        let value_match = ATTRIBUTE_VALUE_RE
+            .captures(&attributes[rest_start..])
+            .and_then(|v| v.name("quoted").or_else(|| v.name("bare")));
+        let value = value_match.map(|v| v.as_str()).unwrap_or_default();
+        debug!("Git attribute {name}");
+        match name {
+            "git" => {
+                debug!("Found url: {value}");
+                spec.url = Some(value.to_string());
+            }
+            "tag" => {
+                debug!("Found tag: {value}");
+                spec.reference = GitRef::Tag(value.to_string());
+            }
+            // the symbol `:control_branch` stands for the environment's branch
+            "branch" if value == ":control_branch" => {
+                debug!("Found control branch");
+                spec.reference = GitRef::ControlBranch;
+            }
+            "branch" => {
+                debug!("Found branch: {value}");
+                spec.reference = GitRef::Branch(value.to_string());
+            }
+            "commit" => {
+                debug!("Found commit hash: {value}");
+                spec.reference = GitRef::Commit(value.to_string());
+            }
+            "ref" => {
+                debug!("Found ref: {value}");
+                spec.reference = GitRef::Ref(value.to_string());
+            }
+            "fallback" | "default_branch" => {
+                debug!("Found fallback branch name: {value}");
+                spec.fallback = Some(value.to_string());
+            }
+            "link" => {
+                debug!("Found link setting");
+                spec.link = true;
+            }
+            "install_path" => {
+                debug!("Found install path: {value}");
+                spec.install_path = Some(value.to_string());
+            }
+            "exclude_spec" => spec.flags.exclude_spec = value == "true",
+            "ignore_unreachable" => spec.flags.ignore_unreachable = value == "true",
+            "use_ssh_agent" => spec.flags.use_ssh_agent = value == "true",
+            "local" => spec.flags.local = value == "true",
+            // makes it a forge module, unless there is a `:git` as well
+            "version" => {
+                debug!("Found version: {value}");
+                module.version = Some(value.to_string());
+                module.version_pin = value_match.map(|v| {
+                    let start = offset + rest_start + v.start();
+                    (line_no, start..start + v.len())
+                });
+            }
+            other => {
+                debug!("Ignoring unhandled git attribute {other} => {value}");
+                parsed.warnings.push(ParseWarning::UnknownGitAttribute {
+                    line: line_no,
+                    name: other.to_string(),
+                });
+            }
+        };
+    }
+}
+
+/// Version of what `parse_puppetfile` returns. Must be bumped whenever it returns something else
+/// for the same content, so `Puppetfile`s parsed before are parsed again.
+///
+/// The package version is checked as well, but it is not raised for every change. Any change to
+/// the patterns, the attributes or the warnings of the parser needs a bump here.
+const PARSE_FORMAT: u32 = 6;
+
+/// How long a parsed `Puppetfile` is kept in the `ParseCache` after it was last used, in seconds
+const PARSE_CACHE_TTL: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ParseCacheEntry {
+    parsed: ParsedPuppetfile,
+    /// When the entry was last used, in seconds since the epoch
+    used: u64,
+}
+
+/// Contents of the file a `ParseCache` is stored in
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ParseCacheFile {
+    /// Version of puppetstuff that wrote the file, a different version may parse differently
+    version: String,
+    /// `PARSE_FORMAT` of the parser that wrote the file, older files have none
+    #[serde(default)]
+    format: u32,
+    entries: HashMap<String, ParseCacheEntry>,
+}
+
+/// Parsed `Puppetfile`s by the id of their git blob. A blob's content never changes, so a
+/// `Puppetfile` that is the same as in an earlier run is not parsed again. Lookups take `&self`,
+/// so it can be shared between threads.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entries: Mutex<HashMap<String, ParseCacheEntry>>,
+}
+
+impl ParseCache {
+    /// Reads the cache from `cache_file`. A missing or broken cache is treated as an empty one.
+    pub fn load<P: AsRef<Path> + fmt::Debug>(cache_file: P) -> Self {
+        debug!("Loading parsed Puppetfiles from {cache_file:?}");
+        let Ok(data) = std::fs::read_to_string(&cache_file) else {
+            debug!("No cache of parsed Puppetfiles found or not readable");
+            return Self::default();
+        };
+        match serde_json::from_str::<ParseCacheFile>(&data) {
+            Ok(file)
+                if file.version == env!("CARGO_PKG_VERSION") && file.format == PARSE_FORMAT =>
+            {
+                Self {
+                    entries: Mutex::new(file.entries),
+                }
+            }
+            Ok(_) => {
+                debug!("The parsed Puppetfiles were cached by another parser, ignoring them");
+                Self::default()
+            }
+            Err(e) => {
+                warn!("Parsing the cache of parsed Puppetfiles failed: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the cache to `cache_file`, leaving out the `Puppetfile`s not used for a while
+    pub fn store<P: AsRef<Path> + fmt::Debug>(&self, cache_file: P) -> std::io::Result<()> {
+        debug!("Writing parsed Puppetfiles to {cache_file:?}");
+        let mut entries = self.entries.lock().unwrap();
+        let now = now();
+        entries.retain(|_, entry| entry.used + PARSE_CACHE_TTL > now);
+        let file = ParseCacheFile {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            format: PARSE_FORMAT,
+            entries: entries.clone(),
+        };
+        std::fs::write(cache_file, serde_json::to_string(&file)?)
+    }
+
+    /// Returns the parsed `content` of the blob `blob_id`, parsing it only if it is not cached
+    pub fn parse(&self, blob_id: &str, content: &str) -> ParsedPuppetfile {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(blob_id) {
+            trace!("Using the cached result for blob {blob_id}");
+            entry.used = now();
+            return entry.parsed.clone();
+        }
+        let parsed = parse_puppetfile(content);
+        self.entries.lock().unwrap().insert(
+            blob_id.to_string(),
+            ParseCacheEntry {
+                parsed: parsed.clone(),
+                used: now(),
+            },
+        );
+        parsed
+    }
+}
+
+/// Seconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forge(name: &str, version: &str) -> Module {
+        Module::Forge(name.to_string(), Version::parse(version).unwrap())
+    }
+
+    fn git(name: &str, url: &str, reference: GitRef) -> Module {
+        Module::Git(
+            name.to_string(),
+            GitSpec {
+                url: Some(url.to_string()),
+                reference,
+                fallback: None,
+                link: false,
+                install_path: None,
+                flags: GitFlags::default(),
+            },
+        )
+    }
+
+    #[test]
+    fn bump_like_the_parser() {
+        let content = "\u{feff}mod 'puppetlabs/stdlib', '9.6.0'
+=begin
+mod 'puppet/systemd', '8.0.0'
+=end
+  mod 'saz/timezone', '7.0.0'
+mod 'puppet/archive', '7.1.0+build1'
+";
+        let bumped = bump_forge_modules(content, |name, _| match name {
+            "puppetlabs-stdlib" => Some(Version::new(9, 7, 0)),
+            "puppet-systemd" => Some(Version::new(8, 1, 0)),
+            "saz-timezone" => Some(Version::new(7, 1, 0)),
+            "puppet-archive" => Some(Version::parse("7.1.0+build2").unwrap()),
+            _ => None,
+        });
+        assert_eq!(
+            bumped.content,
+            content.replace("9.6.0", "9.7.0").replace("7.0.0", "7.1.0")
+        );
+        let lines: Vec<usize> = bumped.bumps.iter().map(|bump| bump.line).collect();
+        assert_eq!(lines, [1, 5]);
+        assert_eq!(bumped.bumps[1].old_line, "  mod 'saz/timezone', '7.0.0'");
+        assert_eq!(bumped.bumps[1].new_line, "  mod 'saz/timezone', '7.1.0'");
+    }
+
+    #[test]
+    fn bump_keeps_everything_else() {
+        let content = r#"# upgraded by hand
+mod 'puppetlabs/stdlib', '9.6.0' # see ticket
+mod "puppet-systemd", "8.1.0"
+mod 'saz-timezone', '7.0.0'
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :tag => 'v1.2.3'
+"#;
+        let bumped = bump_forge_modules(content, |name, _| match name {
+            "puppetlabs-stdlib" => Some(Version::new(9, 7, 0)),
+            "puppet-systemd" => Some(Version::new(8, 0, 0)),
+            _ => None,
+        });
+        assert_eq!(bumped.content, content.replace("9.6.0", "9.7.0"));
+        assert_eq!(bumped.bumps.len(), 1);
+        assert_eq!(bumped.bumps[0].line, 2);
+        assert_eq!(bumped.bumps[0].name, "puppetlabs-stdlib");
+    }
+
+    #[test]
+    fn forge_separators() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', '9.7.0'
+mod "puppet-systemd", "8.1.0"
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                forge("puppet-systemd", "8.1.0")
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn forge_whitespace() {
+        let parsed = parse_puppetfile(
+            "mod 'puppetlabs/stdlib','9.7.0'\nmod\t'puppet/systemd',\t'8.1.0'\nmod 'saz/timezone' , '7.0.0'\n",
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                forge("puppet-systemd", "8.1.0"),
+                forge("saz-timezone", "7.0.0")
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn git_multi_line() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :branch => 'main',
+  :fallback => 'production',
+  :link => true
+mod 'role',
+  :git => "https://git.example.com/role.git",
+  :commit => "0123abc"
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                Module::Git(
+                    "profile".to_string(),
+                    GitSpec {
+                        url: Some("https://git.example.com/profile.git".to_string()),
+                        reference: GitRef::Branch("main".to_string()),
+                        fallback: Some("production".to_string()),
+                        link: true,
+                        install_path: None,
+                        flags: GitFlags::default(),
+                    }
+                ),
+                git(
+                    "role",
+                    "https://git.example.com/role.git",
+                    GitRef::Commit("0123abc".to_string())
+                ),
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn git_single_line() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'profile', :git => 'https://git.example.com/profile.git', :tag => 'v1.2.3'
+mod 'puppetlabs/stdlib', '9.7.0'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                git(
+                    "profile",
+                    "https://git.example.com/profile.git",
+                    GitRef::Tag("v1.2.3".to_string())
+                ),
+                forge("puppetlabs-stdlib", "9.7.0"),
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let parsed = parse_puppetfile(
+            "# a comment\n\n   \nmod 'puppetlabs/stdlib', '9.7.0' # trailing comment\n\t\n# mod 'saz/locales', '4.0.2'\nmod 'profile',\n\n  # the url:\n  :git => 'https://git.example.com/profile.git'\n",
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                git(
+                    "profile",
+                    "https://git.example.com/profile.git",
+                    GitRef::Head
+                ),
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn byte_order_mark_and_shebang() {
+        let parsed = parse_puppetfile("\u{feff}mod 'puppetlabs/stdlib', '9.7.0'\n");
+        assert_eq!(parsed.modules, vec![forge("puppetlabs-stdlib", "9.7.0")]);
+        assert!(parsed.warnings.is_empty());
+
+        let parsed = parse_puppetfile(
+            "\u{feff}#!/usr/bin/env ruby\n  \nmod 'puppetlabs/stdlib', '9.7.0'\nmod 'saz-timezone', '7.0.0'\n",
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                forge("saz-timezone", "7.0.0")
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn block_comments() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', '9.7.0'
+=begin
+mod 'puppet-systemd', '8.1.0'
+mod 'profile',
+  :git => 'https://git.example.com/profile.git'
+=end
+mod 'saz-timezone', '7.0.0'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                forge("saz-timezone", "7.0.0")
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn latest_is_reported() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', :latest
+mod 'saz/locales'
+mod 'puppet/systemd', '8.1.0'
+"#,
+        );
+        assert_eq!(parsed.modules, vec![forge("puppet-systemd", "8.1.0")]);
+        assert_eq!(
+            parsed.warnings,
+            vec![
+                ParseWarning::Unpinned {
+                    line: 2,
+                    name: "puppetlabs-stdlib".to_string()
+                },
+                ParseWarning::Unpinned {
+                    line: 3,
+                    name: "saz-locales".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_version_does_not_panic() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', 'nine'
+mod 'puppet/systemd', '8.1.0'
+"#,
+        );
+        assert_eq!(parsed.modules, vec![forge("puppet-systemd", "8.1.0")]);
+        assert_eq!(
+            parsed.warnings,
+            vec![ParseWarning::InvalidVersion {
+                line: 2,
+                name: "puppetlabs-stdlib".to_string(),
+                version: "nine".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn external_content() {
+        let parsed = parse_puppetfile(
+            r#"
+require_relative 'lib/modules'
+mod 'puppetlabs/stdlib', '9.0.0'
+Dir['modules.d/*.rb'].each { |f| instance_eval(File.read(f)) }
+# eval is fine in a comment
+"#,
+        );
+        assert_eq!(parsed.modules, vec![forge("puppetlabs-stdlib", "9.0.0")]);
+        assert_eq!(
+            parsed.warnings,
+            vec![
+                ParseWarning::ExternalContent {
+                    line: 2,
+                    text: "require_relative 'lib/modules'".to_string()
+                },
+                ParseWarning::ExternalContent {
+                    line: 4,
+                    text: "Dir['modules.d/*.rb'].each { |f| instance_eval(File.read(f)) }"
+                        .to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn version_whitespace() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', '9.0.0 '
+mod 'puppet/systemd', ' 8.1. 0'
+mod 'saz/timezone', :version => ' 7.0.0'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.0.0"),
+                forge("puppet-systemd", "8.1.0"),
+                forge("saz-timezone", "7.0.0")
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn unknown_git_attribute() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :shallow => true
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![git(
+                "profile",
+                "https://git.example.com/profile.git",
+                GitRef::Head
+            )]
+        );
+        assert_eq!(
+            parsed.warnings,
+            vec![ParseWarning::UnknownGitAttribute {
+                line: 4,
+                name: "shallow".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn attribute_outside_module() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', '9.7.0'
+  :git => 'https://git.example.com/stdlib.git'
+mod 'saz/timezone', '7.0.0'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.7.0"),
+                forge("saz-timezone", "7.0.0")
+            ]
+        );
+        assert_eq!(
+            parsed.warnings,
+            vec![ParseWarning::AttributeOutsideModule { line: 3 }]
+        );
+    }
+
+    #[test]
+    fn g10k_flags() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :exclude_spec => true,
+  :ignore_unreachable => true,
+  :use_ssh_agent => false
+mod 'site', :local => true
+"#,
+        );
+        let Module::Git(_, ref spec) = parsed.modules[0] else {
+            panic!("not a git module: {:?}", parsed.modules[0]);
+        };
+        assert_eq!(
+            spec.flags,
+            GitFlags {
+                exclude_spec: true,
+                ignore_unreachable: true,
+                use_ssh_agent: false,
+                local: false,
+            }
+        );
+        let Module::Git(_, ref spec) = parsed.modules[1] else {
+            panic!("not a git module: {:?}", parsed.modules[1]);
+        };
+        assert!(spec.flags.local);
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn unmatched_module_line() {
+        let parsed = parse_puppetfile(
+            r#"
+forge "https://forgeapi.puppet.com"
+mod 'puppetlabs/stdlib' '9.7.0'
+mod 'puppet/systemd', '8.1.0'
+moduledir 'modules'
+"#,
+        );
+        assert_eq!(parsed.modules, vec![forge("puppet-systemd", "8.1.0")]);
+        assert_eq!(
+            parsed.warnings,
+            vec![ParseWarning::UnmatchedModule {
+                line: 3,
+                text: "mod 'puppetlabs/stdlib' '9.7.0'".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn git_ref() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :ref => 'release-2024'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![git(
+                "profile",
+                "https://git.example.com/profile.git",
+                GitRef::Ref("release-2024".to_string())
+            )]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn forge_version_attribute() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'puppetlabs/stdlib', :version => '9.0.0'
+mod 'puppet-systemd',
+  :version => "8.1.0"
+mod 'saz-timezone', :version => :latest
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :version => '1.0.0'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![
+                forge("puppetlabs-stdlib", "9.0.0"),
+                forge("puppet-systemd", "8.1.0"),
+                git(
+                    "profile",
+                    "https://git.example.com/profile.git",
+                    GitRef::Head
+                ),
+            ]
+        );
+        assert_eq!(
+            parsed.warnings,
+            vec![
+                ParseWarning::Unpinned {
+                    line: 5,
+                    name: "saz-timezone".to_string()
+                },
+                ParseWarning::UnknownGitAttribute {
+                    line: 6,
+                    name: "version".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bump_version_attribute() {
+        let content = r#"mod 'puppetlabs/stdlib', :version => '9.6.0'
+mod 'puppet-systemd',
+  :version => "8.0.0" # pinned for a reason
+mod 'saz/timezone', '7.0.0'
+"#;
+        let bumped = bump_forge_modules(content, |name, _| match name {
+            "puppetlabs-stdlib" => Some(Version::new(9, 7, 0)),
+            "puppet-systemd" => Some(Version::new(8, 1, 0)),
+            "saz-timezone" => Some(Version::new(7, 1, 0)),
+            _ => None,
+        });
+        assert_eq!(
+            bumped.content,
+            content
+                .replace("9.6.0", "9.7.0")
+                .replace("8.0.0", "8.1.0")
+                .replace("7.0.0", "7.1.0")
+        );
+        let lines: Vec<usize> = bumped.bumps.iter().map(|bump| bump.line).collect();
+        assert_eq!(lines, [1, 3, 4]);
+        assert_eq!(
+            bumped.bumps[1].new_line,
+            r#"  :version => "8.1.0" # pinned for a reason"#
+        );
+    }
+
+    #[test]
+    fn versioned_tag() {
+        let parsed = parse_puppetfile(
+            "mod 'profile', :git => 'https://git.example.com/profile.git', :tag => 'v1.2.3'\n",
+        );
+        let [Module::Git(_, spec)] = parsed.modules.as_slice() else {
+            panic!("expected a git module, got {:?}", parsed.modules);
+        };
+        assert_eq!(spec.reference, GitRef::Tag("v1.2.3".to_string()));
+        assert_eq!(spec.reference.version(), Some(Version::new(1, 2, 3)));
     }
 
-    modules
+    #[test]
+    fn control_branch() {
+        let parsed = parse_puppetfile(
+            r#"
+mod 'hieradata',
+  :git => 'https://git.example.com/hieradata.git',
+  :branch => :control_branch,
+  :default_branch => 'production'
+"#,
+        );
+        assert_eq!(
+            parsed.modules,
+            vec![Module::Git(
+                "hieradata".to_string(),
+                GitSpec {
+                    url: Some("https://git.example.com/hieradata.git".to_string()),
+                    reference: GitRef::ControlBranch,
+                    fallback: Some("production".to_string()),
+                    link: false,
+                    install_path: None,
+                    flags: GitFlags::default(),
+                }
+            )]
+        );
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn moduledir_and_install_path() {
+        let parsed = parse_puppetfile(
+            r#"
+moduledir 'thirdparty'
+mod 'puppetlabs/stdlib', '9.7.0'
+mod 'profile',
+  :git => 'https://git.example.com/profile.git',
+  :install_path => 'site'
+"#,
+        );
+        assert_eq!(parsed.moduledir.as_deref(), Some("thirdparty"));
+        let Module::Git(_, ref spec) = parsed.modules[1] else {
+            panic!("not a git module: {:?}", parsed.modules[1]);
+        };
+        assert_eq!(spec.install_path.as_deref(), Some("site"));
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("puppetfiles.json");
+        let cache = ParseCache::load(&file);
+        let parsed = cache.parse("abc123", "mod 'puppetlabs/stdlib', :latest\n");
+        assert_eq!(parsed.warnings.len(), 1);
+        cache.store(&file).unwrap();
+
+        // a known blob is not parsed again, the warnings are kept
+        let cache = ParseCache::load(&file);
+        let parsed = cache.parse("abc123", "");
+        assert_eq!(
+            parsed.warnings,
+            [ParseWarning::Unpinned {
+                line: 1,
+                name: "puppetlabs-stdlib".to_string()
+            }]
+        );
+
+        // results of another parser are thrown away
+        let contents = std::fs::read_to_string(&file).unwrap().replace(
+            &format!("\"format\":{PARSE_FORMAT}"),
+            &format!("\"format\":{}", PARSE_FORMAT - 1),
+        );
+        std::fs::write(&file, contents).unwrap();
+        let cache = ParseCache::load(&file);
+        assert!(cache.parse("abc123", "").warnings.is_empty());
+    }
 }
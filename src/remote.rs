@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use git2::build::RepoBuilder;
+use git2::{Cred, CredentialType, FetchOptions, RemoteCallbacks, Repository};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Keys in `~/.ssh` that are tried if the agent did not work out, in this order
+const DEFAULT_SSH_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Builds callbacks that answer credential requests of private remotes.
+///
+/// For SSH remotes, the ssh-agent is asked first, then the default keys in `~/.ssh` are tried and
+/// finally `ssh_key` if given. Each time the remote rejects a credential, the next one is used.
+/// For HTTPS remotes, git's configured credential helper is used.
+pub fn callbacks(ssh_key: Option<&Path>) -> RemoteCallbacks<'static> {
+    // `None` stands for the ssh-agent
+    let mut ssh_candidates: Vec<Option<PathBuf>> = vec![None];
+    if let Some(ssh_dir) = std::env::var_os("HOME").map(|home| Path::new(&home).join(".ssh")) {
+        ssh_candidates.extend(
+            DEFAULT_SSH_KEYS
+                .iter()
+                .map(|key| ssh_dir.join(key))
+                .filter(|key| key.exists())
+                .map(Some),
+        );
+    }
+    if let Some(ssh_key) = ssh_key {
+        ssh_candidates.push(Some(ssh_key.to_path_buf()));
+    }
+    let mut ssh_attempt = 0;
+    let mut helper_tried = false;
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed_types| {
+        let username = username_from_url.unwrap_or("git");
+        if allowed_types.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        if allowed_types.contains(CredentialType::SSH_KEY) {
+            if let Some(candidate) = ssh_candidates.get(ssh_attempt) {
+                ssh_attempt += 1;
+                return match candidate {
+                    None => {
+                        debug!("Trying ssh-agent for {url}");
+                        Cred::ssh_key_from_agent(username)
+                    }
+                    Some(key) => {
+                        debug!("Trying ssh key {key:?} for {url}");
+                        Cred::ssh_key(username, None, key, None)
+                    }
+                };
+            }
+        }
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) && !helper_tried {
+            helper_tried = true;
+            debug!("Trying credential helper for {url}");
+            return Cred::credential_helper(&git2::Config::open_default()?, url, username_from_url);
+        }
+        if allowed_types.contains(CredentialType::DEFAULT) {
+            return Cred::default();
+        }
+        Err(git2::Error::from_str(&format!(
+            "no (more) credentials to try for {url}"
+        )))
+    });
+    callbacks
+}
+
+/// Fetches the branches of `remote_name` so the remote branches are up to date.
+pub fn fetch(
+    repo: &Repository,
+    remote_name: &str,
+    ssh_key: Option<&Path>,
+) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote_name)?;
+    info!(
+        "Fetching {remote_name} from {}",
+        remote.url().unwrap_or("<invalid url>")
+    );
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(ssh_key));
+    // A bare clone has no refspec configured, its local branches mirror the ones of the remote.
+    // Otherwise, an empty list of refspecs uses the ones configured for the remote.
+    let refspecs: &[&str] = if repo.is_bare() && remote.fetch_refspecs()?.is_empty() {
+        &["+refs/heads/*:refs/heads/*"]
+    } else {
+        &[]
+    };
+    remote.fetch(refspecs, Some(&mut options), None)
+}
+
+/// Clones the repository at `url` into `path` as a bare repository, so its remote branches can be
+/// examined without checking out any of them.
+pub fn clone(url: &str, path: &Path, ssh_key: Option<&Path>) -> Result<Repository, git2::Error> {
+    info!("Cloning {url}");
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(ssh_key));
+    RepoBuilder::new()
+        .bare(true)
+        .fetch_options(options)
+        .clone(url, path)
+}
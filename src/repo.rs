@@ -0,0 +1,641 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use git2::BranchType;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tracing::{debug, span, warn};
+
+use crate::models;
+use crate::puppetfile;
+use crate::report::Timings;
+
+/// Name of the pseudo-branch holding the `Puppetfile` in the working tree (`--working`)
+pub const WORKING_BRANCH: &str = "WORKING";
+
+/// Why the `Puppetfile`s of a repository could not be read
+#[derive(Debug)]
+pub enum RepoError {
+    /// The repository could not be opened
+    Open(git2::Error),
+    /// The tag or commit `git_ref` could not be found
+    Resolve { git_ref: String, error: git2::Error },
+    /// The `Puppetfile` at `path` could not be read from the tag or commit `git_ref`
+    Read { git_ref: String, path: String },
+    /// The working tree was asked for, but the repository is bare
+    Bare,
+    /// The commit of `HEAD` could not be found
+    Head(git2::Error),
+    /// The `Puppetfile` at `path` could not be read from the working tree
+    WorkingTree { path: String, error: std::io::Error },
+    /// The branches could not be listed or a commit could not be read
+    Git(git2::Error),
+    /// No branches of `remote` were found (or all were excluded), `remotes` are the ones the
+    /// repository has
+    NoBranches {
+        remote: String,
+        remotes: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for RepoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepoError::Open(e) => write!(f, "Error opening repo: {e}"),
+            RepoError::Resolve { git_ref, error } => write!(f, "Error resolving {git_ref}: {error}"),
+            RepoError::Read { git_ref, path } => write!(f, "Error reading '{path}' of {git_ref}"),
+            RepoError::Bare => write!(
+                f,
+                "Sorry, --working needs a repository with a working tree, this one is bare."
+            ),
+            RepoError::Head(e) => write!(f, "Error finding the commit of HEAD: {e}"),
+            RepoError::WorkingTree { path, error } => {
+                write!(f, "Error reading '{path}' from the working tree: {error}")
+            }
+            RepoError::Git(e) => write!(f, "Error reading the repo: {e}"),
+            RepoError::NoBranches { remote, remotes } if remotes.is_empty() => write!(
+                f,
+                "Sorry, no branches of remote '{remote}' were found, the repository has no remotes."
+            ),
+            RepoError::NoBranches { remote, remotes } => write!(
+                f,
+                "Sorry, no branches of remote '{remote}' were found (or all were excluded). Available remotes: {}",
+                remotes.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
+
+/// Opens the repository at `path`, which may be a working copy (or a directory within it), a
+/// linked worktree or a bare repository.
+pub fn open_repo(path: &str) -> Result<git2::Repository, git2::Error> {
+    git2::Repository::open_ext(
+        path,
+        git2::RepositoryOpenFlags::empty(),
+        std::iter::empty::<&std::ffi::OsStr>(),
+    )
+}
+
+/// Adds the remote's `prefix` to a branch name given by the user, except for the working tree
+pub fn full_branch_name(name: &str, prefix: &str) -> String {
+    if name == WORKING_BRANCH {
+        name.to_string()
+    } else {
+        format!("{prefix}{name}")
+    }
+}
+
+/// Strips the remote's `prefix` (like `origin/`) from a branch name
+pub fn short_branch_name<'a>(name: &'a str, prefix: &str) -> &'a str {
+    name.strip_prefix(prefix).unwrap_or(name)
+}
+
+/// Reads the `Puppetfile` of every branch of `remote_name` that is not excluded. The branches are
+/// parsed in parallel and returned sorted by name, along with the names of all Forge modules.
+/// With `merge_remotes`, the branches of the other remotes are added as if they were branches of
+/// `remote_name`, unless it has a branch of that name itself.
+pub fn parse_git_repo(
+    repo_path: &str,
+    remote_name: &str,
+    puppetfile_path: &str,
+    exclude_branches: &[regex::Regex],
+    merge_remotes: bool,
+    parse_cache_file: &str,
+    timings: &mut Timings,
+) -> Result<(Vec<models::BranchMeta>, HashSet<String>), RepoError> {
+    let repo = open_repo(repo_path).map_err(RepoError::Open)?;
+
+    // A bare clone (`git clone --bare`) has no remote branches, its local branches are the ones of
+    // the remote. They are named like remote branches so they can be selected the same way.
+    let prefix = format!("{remote_name}/");
+    let has_remote_branches = repo
+        .branches(Some(BranchType::Remote))
+        .is_ok_and(|mut branches| branches.next().is_some());
+    let (branch_type, name_prefix) = if repo.is_bare() && !has_remote_branches {
+        debug!("Bare repository without remote branches, using the local branches");
+        (BranchType::Local, prefix.as_str())
+    } else {
+        (BranchType::Remote, "")
+    };
+
+    let remotes: Vec<String> = repo
+        .remotes()
+        .map(|remotes| remotes.iter().flatten().map(String::from).collect())
+        .unwrap_or_default();
+
+    let span = span!(tracing::Level::DEBUG, "enumerate-branches").entered();
+    let enumeration_started = Instant::now();
+    // collect the tips first, the Puppetfiles are read by the thread pool below. With
+    // `merge_remotes`, a branch of another remote is only used if no remote earlier in the order
+    // (`remote_name` first, then by name) has a branch of the same name.
+    let mut tips: Vec<(String, git2::Oid, String)> = vec![];
+    let mut tip_sources: HashMap<String, (bool, String)> = HashMap::new();
+    let branches = repo.branches(Some(branch_type)).map_err(RepoError::Git)?;
+    for (branch, _btype) in branches.into_iter().filter_map(|b| b.ok()) {
+        let name = match branch.name() {
+            Ok(n) => match n {
+                Some(n) => format!("{name_prefix}{n}"),
+                None => {
+                    warn!("Branch name contains invalid characters, skipping");
+                    continue;
+                }
+            },
+            Err(e) => {
+                warn!("Could not retrieve branch name: {e}");
+                continue;
+            }
+        };
+        let (short_name, source) = match name.strip_prefix(&prefix) {
+            Some(short_name) => (short_name, (false, remote_name.to_string())),
+            None if merge_remotes => {
+                // remote names may contain slashes as well, the longest one matching wins
+                let remote = remotes
+                    .iter()
+                    .filter(|remote| {
+                        name.strip_prefix(remote.as_str())
+                            .is_some_and(|rest| rest.starts_with('/'))
+                    })
+                    .max_by_key(|remote| remote.len());
+                match remote {
+                    Some(remote) => (&name[remote.len() + 1..], (true, remote.clone())),
+                    None => {
+                        debug!("Skipping branch {name}");
+                        continue;
+                    }
+                }
+            }
+            None => {
+                debug!("Skipping branch {name}");
+                continue;
+            }
+        };
+        if exclude_branches.iter().any(|re| re.is_match(short_name)) {
+            debug!("Skipping excluded branch {name}");
+            continue;
+        }
+
+        let reference = branch.into_reference();
+
+        if reference.kind() == Some(git2::ReferenceType::Direct) {
+            if let Some(oid) = reference.target() {
+                debug!("{:?} {oid:?}", reference.name());
+                let path = puppetfile_path.replace("{branch}", short_name);
+                let merged_name = format!("{prefix}{short_name}");
+                match tip_sources.get(&merged_name) {
+                    Some(existing) if *existing <= source => {
+                        debug!("Skipping {name}, {} has the same branch", existing.1);
+                        continue;
+                    }
+                    Some(existing) => {
+                        debug!("Replacing the branch of {} by {name}", existing.1);
+                        tips.retain(|(tip_name, _, _)| *tip_name != merged_name);
+                    }
+                    None => (),
+                }
+                tip_sources.insert(merged_name.clone(), source);
+                tips.push((merged_name, oid, path));
+            }
+        }
+    }
+
+    if tips.is_empty() {
+        return Err(RepoError::NoBranches {
+            remote: remote_name.to_string(),
+            remotes,
+        });
+    }
+
+    timings.add("Enumerating branches", enumeration_started.elapsed());
+    drop(span);
+
+    let _span = span!(tracing::Level::DEBUG, "parse-puppetfiles").entered();
+    let parsing_started = Instant::now();
+    // most branches don't change between runs, neither do their Puppetfiles
+    let cache = puppetfile::ParseCache::load(parse_cache_file);
+    // `Repository` is not `Sync`, so each worker thread opens its own handle
+    let git_dir = repo.path().to_path_buf();
+    let mut branch_modules: Vec<models::BranchMeta> = tips
+        .into_par_iter()
+        .map_init(
+            || git2::Repository::open(&git_dir),
+            |thread_repo, (name, oid, path)| match thread_repo {
+                Ok(thread_repo) => read_branch(thread_repo, &cache, name, oid, &path),
+                // a branch left out would make the report look complete when it is not. The
+                // error stays with the handle for the thread's other branches, so it is copied.
+                Err(e) => Err(RepoError::Open(git2::Error::new(
+                    e.code(),
+                    e.class(),
+                    e.message(),
+                ))),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    branch_modules.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Err(e) = cache.store(parse_cache_file) {
+        warn!("Could not write the cache to {parse_cache_file}: {e}");
+    }
+    timings.add("Parsing Puppetfiles", parsing_started.elapsed());
+
+    let forge_names = forge_module_names(&branch_modules);
+    Ok((branch_modules, forge_names))
+}
+
+/// Reads the `Puppetfile` of the tag or commit `git_ref` as if it was the only branch, named like
+/// the reference so it can be selected like one
+pub fn parse_git_ref(
+    repo_path: &str,
+    git_ref: &str,
+    puppetfile_path: &str,
+    parse_cache_file: &str,
+    timings: &mut Timings,
+) -> Result<(Vec<models::BranchMeta>, HashSet<String>), RepoError> {
+    let repo = open_repo(repo_path).map_err(RepoError::Open)?;
+    let commit = repo
+        .revparse_single(git_ref)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|error| RepoError::Resolve {
+            git_ref: git_ref.to_string(),
+            error,
+        })?;
+    let path = puppetfile_path.replace("{branch}", git_ref);
+    let _span = span!(tracing::Level::DEBUG, "parse-puppetfiles").entered();
+    let parsing_started = Instant::now();
+    let cache = puppetfile::ParseCache::load(parse_cache_file);
+    let Some(branch) = read_branch(&repo, &cache, git_ref.to_string(), commit.id(), &path)? else {
+        return Err(RepoError::Read {
+            git_ref: git_ref.to_string(),
+            path,
+        });
+    };
+    if let Err(e) = cache.store(parse_cache_file) {
+        warn!("Could not write the cache to {parse_cache_file}: {e}");
+    }
+    timings.add("Parsing Puppetfiles", parsing_started.elapsed());
+    let branch_modules = vec![branch];
+    let forge_names = forge_module_names(&branch_modules);
+    Ok((branch_modules, forge_names))
+}
+
+/// The names of the Forge modules used by any of the branches
+pub fn forge_module_names(branch_modules: &[models::BranchMeta]) -> HashSet<String> {
+    branch_modules
+        .iter()
+        .flat_map(|branch| &branch.modules)
+        .filter_map(|module| match module {
+            models::Module::Forge(name, _) => Some(name.to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reads and parses the `Puppetfile` at `path` in the commit `oid` that branch `name` points to.
+/// A `Puppetfile` found in `cache` is not parsed again. Branches that can't be read are skipped
+/// with a warning, only a commit without a tree is an error.
+fn read_branch(
+    repo: &git2::Repository,
+    cache: &puppetfile::ParseCache,
+    name: String,
+    oid: git2::Oid,
+    path: &str,
+) -> Result<Option<models::BranchMeta>, RepoError> {
+    let commit = match repo.find_commit(oid) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Could not find the commit of branch {name}: {e}");
+            return Ok(None);
+        }
+    };
+    let author_date = git_time(&commit.author().when());
+    let commit_date = git_time(&commit.time());
+    let author = format!(
+        "{} <{}>",
+        commit.author().name().unwrap_or("Unknown"),
+        commit.author().email().unwrap_or("unknown@unknown")
+    );
+
+    let tree = commit.tree().map_err(RepoError::Git)?;
+    let pf_entry = match tree.get_path(std::path::Path::new(path)) {
+        Ok(te) => te,
+        Err(e) => {
+            warn!("Could not find '{path}' in branch {name}: {e}");
+            return Ok(None);
+        }
+    };
+    let pf_blob = match repo.find_blob(pf_entry.id()) {
+        Ok(b) => b.content().to_owned(),
+        Err(e) => {
+            warn!("Could not get blob for tree entry '{path}' in branch {name}: {e}");
+            return Ok(None);
+        }
+    };
+    let pf_blob = match std::str::from_utf8(&pf_blob) {
+        Ok(b) => b,
+        Err(e) => {
+            warn!("'{path}' in branch {name} is not valid UTF-8: {e}");
+            return Ok(None);
+        }
+    };
+
+    let parsed = report_parse_warnings(
+        &name,
+        path,
+        cache.parse(&pf_entry.id().to_string(), pf_blob),
+    );
+    Ok(Some(models::BranchMeta {
+        name,
+        oid,
+        author_date,
+        commit_date,
+        author,
+        puppetfile: pf_blob.to_string(),
+        moduledir: parsed
+            .moduledir
+            .unwrap_or_else(|| models::DEFAULT_MODULEDIR.to_string()),
+        modules: parsed.modules,
+    }))
+}
+
+/// Returns the path of the `Puppetfile` in the working tree of `repo`, relative to it and in full.
+/// Fails if the repository has no working tree.
+pub fn working_puppetfile(
+    repo: &git2::Repository,
+    puppetfile_path: &str,
+) -> Result<(String, std::path::PathBuf), RepoError> {
+    let Some(workdir) = repo.workdir() else {
+        return Err(RepoError::Bare);
+    };
+    let head = repo.head().ok();
+    let path = puppetfile_path.replace(
+        "{branch}",
+        head.as_ref().and_then(|h| h.shorthand()).unwrap_or("HEAD"),
+    );
+    let full_path = workdir.join(&path);
+    Ok((path, full_path))
+}
+
+/// Reads the `Puppetfile` from the working tree of the repository at `repo_path`, uncommitted
+/// changes included. The commit information is the one of `HEAD`.
+pub fn read_working_tree(
+    repo_path: &str,
+    puppetfile_path: &str,
+) -> Result<models::BranchMeta, RepoError> {
+    let repo = open_repo(repo_path).map_err(RepoError::Open)?;
+    let (path, full_path) = working_puppetfile(&repo, puppetfile_path)?;
+    let commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(RepoError::Head)?;
+    let content = match std::fs::read_to_string(full_path) {
+        Ok(c) => c,
+        Err(error) => return Err(RepoError::WorkingTree { path, error }),
+    };
+
+    let parsed = parse_branch_puppetfile(WORKING_BRANCH, &path, &content);
+    let commit_author = commit.author();
+    Ok(models::BranchMeta {
+        name: WORKING_BRANCH.to_string(),
+        oid: commit.id(),
+        author_date: git_time(&commit_author.when()),
+        commit_date: git_time(&commit.time()),
+        author: format!(
+            "{} <{}>",
+            commit_author.name().unwrap_or("Unknown"),
+            commit_author.email().unwrap_or("unknown@unknown")
+        ),
+        puppetfile: content,
+        moduledir: parsed
+            .moduledir
+            .unwrap_or_else(|| models::DEFAULT_MODULEDIR.to_string()),
+        modules: parsed.modules,
+    })
+}
+
+/// Parses the `Puppetfile` at `path` of branch `name`, logging the problems found in it
+fn parse_branch_puppetfile(name: &str, path: &str, content: &str) -> puppetfile::ParsedPuppetfile {
+    report_parse_warnings(name, path, puppetfile::parse_puppetfile(content))
+}
+
+/// Logs the problems found in the `Puppetfile` at `path` of branch `name`
+fn report_parse_warnings(
+    name: &str,
+    path: &str,
+    parsed: puppetfile::ParsedPuppetfile,
+) -> puppetfile::ParsedPuppetfile {
+    for warning in &parsed.warnings {
+        warn!("'{path}' in branch {name}: {warning}");
+    }
+    // an empty column looks the same as a branch that uses no modules at all
+    if parsed.modules.is_empty() {
+        warn!("'{path}' in branch {name} does not declare any module, is it empty or malformed?");
+    }
+    parsed
+}
+
+/// Converts a git timestamp to a `DateTime`, falling back to the epoch if it is out of range
+fn git_time(time: &git2::Time) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(time.seconds(), 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commits `puppetfile` as the `Puppetfile` of the repository (on top of `parent`, if any)
+    /// without touching the working tree, and points `reference` to the commit
+    fn commit(
+        repo: &git2::Repository,
+        reference: &str,
+        puppetfile: &str,
+        parent: Option<git2::Oid>,
+    ) -> git2::Oid {
+        let blob = repo.blob(puppetfile.as_bytes()).unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("Puppetfile", blob, 0o100644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature =
+            git2::Signature::new("Jane Doe", "jane@example.com", &git2::Time::new(0, 0)).unwrap();
+        let parents: Vec<git2::Commit> = parent
+            .map(|oid| repo.find_commit(oid).unwrap())
+            .into_iter()
+            .collect();
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        let oid = repo
+            .commit(None, &signature, &signature, "update", &tree, &parents)
+            .unwrap();
+        repo.reference(reference, oid, true, "test").unwrap();
+        oid
+    }
+
+    /// A repository with the remote branches `origin/dev`, `origin/production` and
+    /// `upstream/feature`, along with the file for the parse cache
+    fn fixture() -> (tempfile::TempDir, git2::Repository, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path().join("repo")).unwrap();
+        repo.remote("origin", "https://git.example.com/origin.git")
+            .unwrap();
+        repo.remote("upstream", "https://git.example.com/upstream.git")
+            .unwrap();
+        let production = commit(
+            &repo,
+            "refs/remotes/origin/production",
+            "mod 'puppetlabs/stdlib', '9.6.0'\n",
+            None,
+        );
+        commit(
+            &repo,
+            "refs/remotes/origin/dev",
+            "mod 'puppetlabs/stdlib', '9.7.0'\nmod 'puppet/systemd', '8.1.0'\n",
+            Some(production),
+        );
+        commit(
+            &repo,
+            "refs/remotes/upstream/feature",
+            "mod 'saz/timezone', '7.0.0'\n",
+            Some(production),
+        );
+        let cache = dir.path().join("puppetfiles.json");
+        (dir, repo, cache.to_string_lossy().into_owned())
+    }
+
+    fn path(repo: &git2::Repository) -> String {
+        repo.workdir().unwrap().to_string_lossy().into_owned()
+    }
+
+    fn names(branches: &[models::BranchMeta]) -> Vec<&str> {
+        branches.iter().map(|branch| branch.name.as_str()).collect()
+    }
+
+    #[test]
+    fn branch_names() {
+        assert_eq!(full_branch_name("dev", "origin/"), "origin/dev");
+        assert_eq!(full_branch_name(WORKING_BRANCH, "origin/"), WORKING_BRANCH);
+        assert_eq!(short_branch_name("origin/dev", "origin/"), "dev");
+        assert_eq!(short_branch_name(WORKING_BRANCH, "origin/"), WORKING_BRANCH);
+    }
+
+    #[test]
+    fn remote_branches() {
+        let (_dir, repo, cache) = fixture();
+        let mut timings = Timings::default();
+        let (branches, forge_names) = parse_git_repo(
+            &path(&repo),
+            "origin",
+            "Puppetfile",
+            &[],
+            false,
+            &cache,
+            &mut timings,
+        )
+        .unwrap();
+        assert_eq!(names(&branches), ["origin/dev", "origin/production"]);
+        assert_eq!(branches[1].author, "Jane Doe <jane@example.com>");
+        assert_eq!(
+            forge_names,
+            HashSet::from([
+                "puppetlabs-stdlib".to_string(),
+                "puppet-systemd".to_string()
+            ])
+        );
+        let steps: Vec<&str> = timings.steps.iter().map(|(step, _)| *step).collect();
+        assert_eq!(steps, ["Enumerating branches", "Parsing Puppetfiles"]);
+
+        // the branches of the other remotes are added as if they were branches of `origin`
+        let exclude = [regex::Regex::new("^prod").unwrap()];
+        let (branches, _) = parse_git_repo(
+            &path(&repo),
+            "origin",
+            "Puppetfile",
+            &exclude,
+            true,
+            &cache,
+            &mut timings,
+        )
+        .unwrap();
+        assert_eq!(names(&branches), ["origin/dev", "origin/feature"]);
+    }
+
+    #[test]
+    fn no_branches() {
+        let (_dir, repo, cache) = fixture();
+        let result = parse_git_repo(
+            &path(&repo),
+            "nope",
+            "Puppetfile",
+            &[],
+            false,
+            &cache,
+            &mut Timings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(RepoError::NoBranches { ref remote, ref remotes }) if remote == "nope" && *remotes == ["origin", "upstream"]
+        ));
+    }
+
+    #[test]
+    fn git_ref() {
+        let (_dir, repo, cache) = fixture();
+        let mut timings = Timings::default();
+        let (branches, forge_names) = parse_git_ref(
+            &path(&repo),
+            "origin/dev~1",
+            "Puppetfile",
+            &cache,
+            &mut timings,
+        )
+        .unwrap();
+        assert_eq!(names(&branches), ["origin/dev~1"]);
+        assert_eq!(
+            forge_names,
+            HashSet::from(["puppetlabs-stdlib".to_string()])
+        );
+
+        let result = parse_git_ref(&path(&repo), "nope", "Puppetfile", &cache, &mut timings);
+        assert!(matches!(result, Err(RepoError::Resolve { .. })));
+        let result = parse_git_ref(&path(&repo), "origin/dev", "missing", &cache, &mut timings);
+        assert!(matches!(result, Err(RepoError::Read { .. })));
+    }
+
+    #[test]
+    fn working_tree() {
+        let (_dir, repo, _) = fixture();
+        // without a HEAD, there is no commit to take the information from
+        let result = read_working_tree(&path(&repo), "Puppetfile");
+        assert!(matches!(result, Err(RepoError::Head(_))));
+
+        let head = repo.refname_to_id("refs/remotes/origin/dev").unwrap();
+        repo.set_head_detached(head).unwrap();
+        let result = read_working_tree(&path(&repo), "Puppetfile");
+        assert!(matches!(result, Err(RepoError::WorkingTree { .. })));
+
+        // uncommitted changes are included
+        std::fs::write(
+            repo.workdir().unwrap().join("Puppetfile"),
+            "mod 'puppet/archive', '7.1.0'\n",
+        )
+        .unwrap();
+        let working = read_working_tree(&path(&repo), "Puppetfile").unwrap();
+        assert_eq!(working.name, WORKING_BRANCH);
+        assert_eq!(working.oid, head);
+        assert_eq!(
+            working.modules,
+            [models::Module::Forge(
+                "puppet-archive".to_string(),
+                semver::Version::new(7, 1, 0)
+            )]
+        );
+    }
+
+    #[test]
+    fn bare_working_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        git2::Repository::init_bare(dir.path()).unwrap();
+        let result = read_working_tree(&dir.path().to_string_lossy(), "Puppetfile");
+        assert!(matches!(result, Err(RepoError::Bare)));
+    }
+}
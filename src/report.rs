@@ -0,0 +1,1787 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use chrono::{DateTime, Utc};
+use semver::Version;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tracing::{debug, span, trace, warn};
+
+use crate::forge;
+use crate::models;
+use crate::puppetfile;
+use crate::repo;
+
+/// Shown instead of the latest version of a module that has not published a release yet
+pub const NO_RELEASES: &str = "no releases";
+
+/// Shown instead of the latest version of a module that could not be looked up (`--keep-going`)
+pub const LOOKUP_FAILED: &str = "lookup failed";
+
+/// What the versions in the branches are compared against
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Baseline {
+    /// The latest release on the Forge, to plan upgrades
+    Forge,
+    /// The newest version used by any branch, to detect drift between branches
+    MaxInUse,
+}
+
+/// Freshness of a version, decides the colour or marker of its cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// Deprecated on the Forge
+    Deprecated,
+    /// Older than the minimum set with `--min-version`
+    BelowMinimum,
+    /// No release within `--stale-after` days, the module may be unmaintained
+    Stale,
+    /// Older than the baseline (for the Forge: newer than any version in use)
+    Behind,
+    /// Newer than the baseline, e.g. a pre-release or a yanked release
+    Ahead,
+    /// A git module pinned differently than in most branches (`--include-git`)
+    Differs,
+    /// Same as the baseline
+    Current,
+}
+
+impl Status {
+    pub const ALL: [Status; 7] = [
+        Status::Deprecated,
+        Status::BelowMinimum,
+        Status::Stale,
+        Status::Behind,
+        Status::Ahead,
+        Status::Differs,
+        Status::Current,
+    ];
+
+    /// Describes the status for the legend, `baseline` being what the versions are compared with
+    pub fn description(self, baseline: Baseline) -> &'static str {
+        match (self, baseline) {
+            (Status::Deprecated, _) => "deprecated on the Forge",
+            (Status::BelowMinimum, _) => "older than the minimum version required",
+            (Status::Stale, _) => "no release for a long time, the module may be unmaintained",
+            (Status::Behind, Baseline::Forge) => {
+                "older than the latest release on the Forge (Forge column: no branch uses it)"
+            }
+            (Status::Behind, Baseline::MaxInUse) => {
+                "older than the newest version used by any branch (Forge column: no branch uses the latest release)"
+            }
+            (Status::Ahead, Baseline::Forge) => "newer than the latest release on the Forge",
+            (Status::Ahead, Baseline::MaxInUse) => "newer than the newest version used by any branch",
+            (Status::Differs, _) => "git module pinned differently than in most branches",
+            (Status::Current, Baseline::Forge) => "the latest release on the Forge",
+            (Status::Current, Baseline::MaxInUse) => "the newest version used by any branch",
+        }
+    }
+}
+
+/// A cell showing a version, `status` decides its colour or marker (none if `None`)
+#[derive(Clone)]
+pub struct VersionCell {
+    pub text: String,
+    pub status: Option<Status>,
+}
+
+/// A Forge module of the branches view along with the versions the branches use
+#[derive(Debug)]
+pub struct ModuleRow {
+    pub name: String,
+    /// `None` if the module has no releases or could not be looked up
+    pub forge_version: Option<Version>,
+    pub lookup_failed: bool,
+    pub forge_deprecated: bool,
+    pub forge_stale: bool,
+    pub max_in_use_version: Version,
+    pub min_in_use_version: Option<Version>,
+    pub branch_versions: HashMap<String, Option<Version>>,
+}
+
+impl ModuleRow {
+    /// Collects the versions of the module `name` that `branches` use, without looking it up
+    pub fn new(name: &str, branches: &[models::BranchMeta]) -> Self {
+        let mut row = ModuleRow {
+            name: name.to_string(),
+            forge_version: None,
+            lookup_failed: false,
+            forge_deprecated: false,
+            forge_stale: false,
+            max_in_use_version: Version::new(0, 0, 0),
+            min_in_use_version: None,
+            branch_versions: HashMap::new(),
+        };
+        for branch in branches {
+            debug!("Branch {}", branch.name);
+            let version = branch.modules.iter().find_map(|module| match module {
+                models::Module::Forge(module_name, version) if module_name == name => Some(version),
+                _ => None,
+            });
+            if let Some(version) = version {
+                row.max_in_use_version = std::cmp::max(version.clone(), row.max_in_use_version);
+                row.min_in_use_version = Some(match row.min_in_use_version {
+                    Some(min) => std::cmp::min(version.clone(), min),
+                    None => version.clone(),
+                });
+                row.branch_versions
+                    .insert(branch.name.clone(), Some(version.clone()));
+            }
+        }
+        row
+    }
+
+    /// How far the oldest version in use is behind the latest release on the Forge
+    pub fn lag(&self) -> models::VersionLag {
+        self.min_in_use_version
+            .as_ref()
+            .zip(self.forge_version.as_ref())
+            .map(|(min, forge)| models::VersionLag::between(min, forge))
+            .unwrap_or_default()
+    }
+
+    /// The cell of the latest release on the Forge, marked if no branch uses it
+    pub fn forge_cell(&self) -> VersionCell {
+        let status = latest_status(self.forge_deprecated, self.forge_stale).or(self
+            .forge_version
+            .as_ref()
+            .is_some_and(|forge_version| {
+                forge_version
+                    .cmp_precedence(&self.max_in_use_version)
+                    .is_gt()
+            })
+            .then_some(Status::Behind));
+        VersionCell {
+            text: match self.lookup_failed {
+                true => LOOKUP_FAILED.to_string(),
+                false => self
+                    .forge_version
+                    .as_ref()
+                    .map_or(NO_RELEASES.to_string(), Version::to_string),
+            },
+            status,
+        }
+    }
+
+    /// The cell of the version `branch` uses compared with `baseline`, `None` if the branch does
+    /// not use the module. `minimum` is the version required by `--min-version`, with
+    /// `show_target` an outdated version shows the latest release as well. Also tells whether the
+    /// version counts as outdated in the totals.
+    pub fn branch_cell(
+        &self,
+        branch: &str,
+        baseline: Baseline,
+        minimum: Option<&Version>,
+        show_target: bool,
+    ) -> Option<(VersionCell, bool)> {
+        let branch_version = self.branch_versions.get(branch)?.as_ref()?;
+        // a module without releases can't be compared with the Forge
+        let baseline = match baseline {
+            Baseline::Forge => self.forge_version.as_ref(),
+            Baseline::MaxInUse => Some(&self.max_in_use_version),
+        };
+        let below_minimum = minimum.is_some_and(|min| branch_version.cmp_precedence(min).is_lt());
+        let status = match baseline.map(|b| branch_version.cmp_precedence(b)) {
+            _ if below_minimum => Some(Status::BelowMinimum),
+            Some(std::cmp::Ordering::Less) => Some(Status::Behind),
+            Some(std::cmp::Ordering::Greater) => Some(Status::Ahead),
+            Some(std::cmp::Ordering::Equal) => Some(Status::Current),
+            None => None,
+        };
+        let outdated = matches!(status, Some(Status::Behind | Status::BelowMinimum));
+        let target = self.forge_version.as_ref().filter(|forge_version| {
+            show_target && outdated && branch_version.cmp_precedence(forge_version).is_lt()
+        });
+        let cell = VersionCell {
+            text: match target {
+                Some(target) => format!("{branch_version} (→{target})"),
+                None => branch_version.to_string(),
+            },
+            status,
+        };
+        // the newest version of a deprecated or stale module is no better
+        Some((cell, outdated || self.forge_deprecated || self.forge_stale))
+    }
+}
+
+/// Builds the rows of the modules `names` for the branches view, sorted by name. The modules of
+/// `failed_names` could not be looked up and are only marked as such. Modules without a release
+/// since `stale_before` are stale.
+pub fn module_rows(
+    api: &forge::ForgeApi,
+    names: &[String],
+    failed_names: &[String],
+    branches: &[models::BranchMeta],
+    stale_before: Option<DateTime<Utc>>,
+) -> Vec<ModuleRow> {
+    let mut names: Vec<&String> = names.iter().chain(failed_names).collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let _span = span!(tracing::Level::DEBUG, "forge-mod-loop", mod_name = name).entered();
+            let lookup_failed = failed_names.contains(name);
+            // the version and the deprecation come from the same lookup
+            let entry = match lookup_failed {
+                true => None,
+                false => lookup_or_warn("entry", name, api.get_entry(name).map(Some)),
+            };
+            let mut row = ModuleRow::new(name, branches);
+            row.forge_version = entry.as_ref().and_then(|entry| entry.version.clone());
+            row.lookup_failed = lookup_failed;
+            row.forge_deprecated = entry.is_some_and(|entry| entry.deprecation.is_some());
+            row.forge_stale = !lookup_failed && is_stale(api, name, stale_before);
+            row
+        })
+        .collect()
+}
+
+/// A module of the branches view: its name and the cells of the Forge and of each branch
+pub type ModuleCells = (TableCell, Vec<Option<VersionCell>>);
+
+/// Builds the cells of the branches view from `rows`: each module with the cell of the Forge
+/// followed by one cell per branch of `branches`. `min_versions` are the versions required by
+/// `--min-version`, see `ModuleRow::branch_cell` for `baseline` and `show_target`. Also returns
+/// the (current, outdated) versions of each branch, for `--totals`.
+pub fn branch_cells(
+    rows: Vec<ModuleRow>,
+    branches: &[String],
+    baseline: Baseline,
+    min_versions: &HashMap<&str, &Version>,
+    show_target: bool,
+) -> (Vec<ModuleCells>, Vec<(usize, usize)>) {
+    let mut branch_totals = vec![(0, 0); branches.len()];
+    let cells = rows
+        .into_iter()
+        .map(|row| {
+            trace!("{row:?}");
+            let mut cells = vec![Some(row.forge_cell())];
+            for (branch, totals) in branches.iter().zip(branch_totals.iter_mut()) {
+                debug!("branch {branch}");
+                let minimum = min_versions.get(row.name.as_str()).copied();
+                let cell = row.branch_cell(branch, baseline, minimum, show_target);
+                cells.push(cell.map(|(cell, outdated)| {
+                    match outdated {
+                        true => totals.1 += 1,
+                        false => totals.0 += 1,
+                    }
+                    cell
+                }));
+            }
+            (TableCell::Module(row.name), cells)
+        })
+        .collect();
+    (cells, branch_totals)
+}
+
+/// Points out the successors of the deprecated modules of `rows`, moving to them is the usual way
+/// out even if the deprecated view is not looked at
+pub fn warn_replacements(api: &forge::ForgeApi, rows: &[ModuleRow]) {
+    for row in rows.iter().filter(|row| row.forge_deprecated) {
+        let deprecation = lookup_or_warn("deprecation", &row.name, api.get_deprecation(&row.name));
+        if let Some(replacement) = deprecation.and_then(|d| d.replacement) {
+            warn!("{} is deprecated, consider {replacement} instead", row.name);
+        }
+    }
+}
+
+/// What the interactive table (`--tui`) shows about the module `name` when it is selected
+pub fn module_details(api: &forge::ForgeApi, name: &str) -> Vec<(&'static str, String)> {
+    let deprecation = lookup_or_warn("deprecation", name, api.get_deprecation(name));
+    let replacement = deprecation.as_ref().and_then(|d| d.replacement.clone());
+    let deprecation = match deprecation {
+        Some(deprecation) => [
+            deprecation.at.map(|at| at.format("%Y-%m-%d").to_string()),
+            deprecation.reason,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>()
+        .join(", "),
+        None => "no".to_string(),
+    };
+    let mut details = vec![
+        (
+            "Latest",
+            lookup_or_warn("latest version", name, latest_version_text(api, name)),
+        ),
+        (
+            "Released",
+            release_date(api, name).map_or("unknown".to_string(), |released| {
+                released.format("%Y-%m-%d").to_string()
+            }),
+        ),
+        ("Deprecated", deprecation),
+        (
+            "Endorsement",
+            endorsement_label(
+                lookup_or_warn("endorsement", name, api.get_endorsement(name)).as_deref(),
+            )
+            .to_string(),
+        ),
+    ];
+    if let Some(replacement) = replacement {
+        details.insert(3, ("Replacement", replacement));
+    }
+    details
+}
+
+/// The policies of `--fail-on` checked by the branches view
+#[derive(Default)]
+pub struct Policy<'a> {
+    /// A deprecated module must not be used
+    pub deprecated: bool,
+    /// A version must not be further behind the latest release than this
+    pub max_behind: Option<models::VersionLag>,
+    /// A version must not be older than the minimum of its module
+    pub below_minimum: bool,
+    /// The minimum version of each module, see `--min-version`
+    pub min_versions: HashMap<&'a str, &'a Version>,
+}
+
+/// Checks the modules of `rows` used by `branches` against `policy`. Returns the key figures
+/// (without the violations) and the violations found, the branch names are shown without
+/// `prefix`.
+pub fn check_policies(
+    rows: &[ModuleRow],
+    branches: &[String],
+    prefix: &str,
+    policy: &Policy,
+) -> (Summary, Vec<String>) {
+    let mut figures = Summary::default();
+    let mut violations = vec![];
+    for mod_row in rows {
+        let mut used = false;
+        let mut outdated = false;
+        for branch_name in branches {
+            let Some(Some(branch_version)) = mod_row.branch_versions.get(branch_name) else {
+                continue;
+            };
+            used = true;
+            let branch = repo::short_branch_name(branch_name, prefix);
+            if policy.deprecated && mod_row.forge_deprecated {
+                violations.push(format!(
+                    "{} is deprecated but used in branch {branch}",
+                    mod_row.name
+                ));
+            }
+            // without releases, nothing is outdated
+            if let Some(forge_version) = &mod_row.forge_version {
+                // build metadata (`1.2.0+deploy5`) does not count, see `cmp_precedence`
+                outdated |= branch_version.cmp_precedence(forge_version).is_lt();
+                let lag = models::VersionLag::between(branch_version, forge_version);
+                if policy.max_behind.is_some_and(|max_behind| lag > max_behind) {
+                    violations.push(format!(
+                        "{} is at {branch_version} in branch {branch}, but {forge_version} is available",
+                        mod_row.name
+                    ));
+                }
+            }
+            if let Some(min) = policy.min_versions.get(mod_row.name.as_str()) {
+                if policy.below_minimum && branch_version.cmp_precedence(min).is_lt() {
+                    violations.push(format!(
+                        "{} is at {branch_version} in branch {branch}, but at least {min} is required",
+                        mod_row.name
+                    ));
+                }
+            }
+        }
+        if used {
+            figures.modules += 1;
+            figures.outdated += usize::from(outdated);
+            figures.deprecated += usize::from(mod_row.forge_deprecated);
+        }
+    }
+    (figures, violations)
+}
+
+/// The optional columns of the latest view
+#[derive(Default)]
+pub struct LatestColumns<'a> {
+    /// The module's endorsement by Puppet
+    pub endorsement: bool,
+    /// Whether the latest release supports this version of Puppet
+    pub puppet_version: Option<&'a Version>,
+    /// The operating systems (name and optional release) the latest release does not claim to
+    /// support
+    pub os: &'a [(String, Option<String>)],
+    /// The repositories using the module, each with the Forge modules it uses. Only shown if there
+    /// are several of them.
+    pub repo_usage: &'a [(String, HashSet<String>)],
+}
+
+/// The latest view along with the figures of its modules
+pub struct LatestTable {
+    pub header: Vec<TableCell>,
+    pub rows: Vec<Vec<TableCell>>,
+    /// Number of modules, including the ones left out by the limit
+    pub total: usize,
+    /// Number of deprecated modules
+    pub deprecated: usize,
+    /// Number of shown modules whose latest release does not support the Puppet version
+    pub unsupported: usize,
+    /// Which operating systems the latest release of a shown module does not claim to support,
+    /// one line per module
+    pub missing_os: Vec<String>,
+}
+
+/// Builds the latest view of the modules `names` with the optional `columns`, showing at most
+/// `limit` of them. The modules of `failed_names` could not be looked up and are only marked as
+/// such. Modules without a release since `stale_before` are stale.
+pub fn latest_table(
+    api: &forge::ForgeApi,
+    names: &[String],
+    failed_names: &[String],
+    columns: &LatestColumns,
+    limit: Option<usize>,
+    stale_before: Option<DateTime<Utc>>,
+) -> LatestTable {
+    // with several repositories, show which of them use a module
+    let several_repos = columns.repo_usage.len() > 1;
+    let mut header = vec!["Name".to_string(), "Latest".to_string()];
+    if columns.endorsement {
+        header.push("Endorsement".to_string());
+    }
+    if let Some(puppet) = columns.puppet_version {
+        header.push(format!("Puppet {puppet}"));
+    }
+    if !columns.os.is_empty() {
+        header.push("OS support".to_string());
+    }
+    if several_repos {
+        header.push("Repositories".to_string());
+    }
+    let header: Vec<TableCell> = header.into_iter().map(TableCell::Text).collect();
+    let mut table = LatestTable {
+        rows: vec![],
+        total: 0,
+        deprecated: names
+            .iter()
+            .filter(|name| lookup_or_warn("deprecation", name, api.is_deprecated(name)))
+            .count(),
+        unsupported: 0,
+        missing_os: vec![],
+        header,
+    };
+    let mut names: Vec<&String> = names.iter().chain(failed_names).collect();
+    names.sort();
+    table.total = names.len();
+    for name in names.into_iter().take(limit.unwrap_or(usize::MAX)) {
+        if failed_names.contains(name) {
+            let mut row = vec![
+                TableCell::Module(name.clone()),
+                TableCell::Version(Some(VersionCell {
+                    text: LOOKUP_FAILED.to_string(),
+                    status: None,
+                })),
+            ];
+            row.resize(table.header.len(), TableCell::Text(String::new()));
+            table.rows.push(row);
+            continue;
+        }
+        let version = VersionCell {
+            text: lookup_or_warn("latest version", name, latest_version_text(api, name)),
+            status: latest_status(
+                lookup_or_warn("deprecation", name, api.is_deprecated(name)),
+                is_stale(api, name, stale_before),
+            ),
+        };
+        let mut row = vec![
+            TableCell::Module(name.clone()),
+            TableCell::Version(Some(version)),
+        ];
+        if columns.endorsement {
+            row.push(TableCell::Text(
+                endorsement_label(
+                    lookup_or_warn("endorsement", name, api.get_endorsement(name)).as_deref(),
+                )
+                .to_string(),
+            ));
+        }
+        if let Some(puppet) = columns.puppet_version {
+            let (text, supported) = puppet_support(api, name, puppet);
+            if supported == Some(false) {
+                table.unsupported += 1;
+            }
+            row.push(TableCell::Text(text));
+        }
+        if !columns.os.is_empty() {
+            let missing = missing_os_support(api, name, columns.os);
+            let text = match missing {
+                None => "unknown".to_string(),
+                Some(ref missing) if missing.is_empty() => "yes".to_string(),
+                Some(ref missing) => format!("no {}", missing.join(", ")),
+            };
+            if let Some(missing) = missing.filter(|missing| !missing.is_empty()) {
+                table.missing_os.push(format!(
+                    "the latest release of {name} does not claim to support {}",
+                    missing.join(", ")
+                ));
+            }
+            row.push(TableCell::Text(text));
+        }
+        if several_repos {
+            let repos: Vec<&str> = columns
+                .repo_usage
+                .iter()
+                .filter(|(_, names)| names.contains(name))
+                .map(|(label, _)| label.as_str())
+                .collect();
+            row.push(TableCell::Text(repos.join(", ")));
+        }
+        table.rows.push(row);
+    }
+    table
+}
+
+/// Describes the endorsement of a module by Puppet
+pub fn endorsement_label(endorsement: Option<&str>) -> &str {
+    match endorsement {
+        Some("supported") => "✔ Supported",
+        Some("approved") => "✔ Approved",
+        Some("partner") => "✔ Partner",
+        Some(other) => other,
+        None => "",
+    }
+}
+
+/// Returns the latest version of the module `name` on the Forge, `None` if it has no releases
+pub fn latest_version(
+    api: &forge::ForgeApi,
+    name: &str,
+) -> Result<Option<Version>, forge::ForgeError> {
+    match api.get_version(name) {
+        Ok(version) => Ok(Some(version)),
+        Err(forge::ForgeError::NoReleases(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns when the latest version of the module `name` was released, `None` if it has no releases
+/// or the date is unknown
+pub fn release_date(api: &forge::ForgeApi, name: &str) -> Option<DateTime<Utc>> {
+    match api.get_release_date(name) {
+        Err(forge::ForgeError::NoReleases(_)) => None,
+        result => lookup_or_warn("release date", name, result),
+    }
+}
+
+/// Returns the latest version of the module `name` on the Forge for display
+pub fn latest_version_text(api: &forge::ForgeApi, name: &str) -> Result<String, forge::ForgeError> {
+    Ok(latest_version(api, name)?.map_or(NO_RELEASES.to_string(), |version| version.to_string()))
+}
+
+/// Returns the result of a Forge lookup, or the default (nothing) with a warning if it failed
+pub fn lookup_or_warn<T: Default, E: std::fmt::Display>(
+    what: &str,
+    name: &str,
+    result: Result<T, E>,
+) -> T {
+    result.unwrap_or_else(|e| {
+        warn!("Could not look up the {what} of {name}: {e}");
+        T::default()
+    })
+}
+
+/// Tells whether the latest release of the module `name` declares support for Puppet `target`,
+/// as text for the latest view along with the answer. The answer is `None` if it is unknown, e.g.
+/// because the release does not say.
+pub fn puppet_support(
+    api: &forge::ForgeApi,
+    name: &str,
+    target: &Version,
+) -> (String, Option<bool>) {
+    let unknown = ("unknown".to_string(), None);
+    let Some((version, metadata)) = latest_metadata(api, name) else {
+        return unknown;
+    };
+    let Some(requirement) = metadata
+        .requirements
+        .iter()
+        .find(|requirement| requirement.name == "puppet")
+        .and_then(|requirement| requirement.version_requirement.as_deref())
+    else {
+        return unknown;
+    };
+    match forge::parse_version_requirement(requirement) {
+        Ok(req) if req.matches(target) => ("yes".to_string(), Some(true)),
+        Ok(_) => (format!("no ({requirement})"), Some(false)),
+        Err(e) => {
+            warn!("{name} {version} has an invalid Puppet requirement '{requirement}': {e}");
+            unknown
+        }
+    }
+}
+
+/// Returns the operating systems of `required` (name and optional release) that the latest release
+/// of the module `name` does not claim to support, formatted like `RedHat 9`. `None` if it is
+/// unknown, e.g. because the release does not list any.
+pub fn missing_os_support(
+    api: &forge::ForgeApi,
+    name: &str,
+    required: &[(String, Option<String>)],
+) -> Option<Vec<String>> {
+    let (_, metadata) = latest_metadata(api, name)?;
+    if metadata.operatingsystem_support.is_empty() {
+        return None;
+    }
+    let missing = required
+        .iter()
+        .filter(|(os, release)| {
+            !metadata
+                .operatingsystem_support
+                .iter()
+                .any(|supported| supported.covers(os, release.as_deref()))
+        })
+        .map(|(os, release)| match release {
+            Some(release) => format!("{os} {release}"),
+            None => os.clone(),
+        })
+        .collect();
+    Some(missing)
+}
+
+/// Returns the latest release of the module `name` along with its metadata, `None` if there is no
+/// release or the metadata could not be fetched
+pub fn latest_metadata(
+    api: &forge::ForgeApi,
+    name: &str,
+) -> Option<(Version, forge::ReleaseMetadata)> {
+    let version = latest_version(api, name).ok()??;
+    match api.get_release_metadata(name, &version) {
+        Ok(metadata) => Some((version, metadata)),
+        Err(e) => {
+            warn!("Could not get the metadata of {name} {version}: {e}");
+            None
+        }
+    }
+}
+
+/// Tells whether the module `name` did not publish a release since `stale_before`, and may be
+/// unmaintained. A module without releases (or whose release date is unknown) is not stale.
+pub fn is_stale(api: &forge::ForgeApi, name: &str, stale_before: Option<DateTime<Utc>>) -> bool {
+    stale_before.is_some_and(|stale_before| {
+        release_date(api, name).is_some_and(|released| released < stale_before)
+    })
+}
+
+/// Builds the rows of the git modules for the branches view: the name of each module and its
+/// cells, a marker followed by one cell per branch of `branches`. Git modules have no Forge
+/// version. The ones pinned to semver tags are compared with the newest tag in use, the others
+/// (only with `include_git`) with the reference most branches use. Adds the (current, outdated)
+/// versions to `branch_totals`, one per branch.
+pub fn git_rows(
+    branch_modules: &[models::BranchMeta],
+    branches: &[String],
+    include_git: bool,
+    branch_totals: &mut [(usize, usize)],
+) -> Vec<(String, Vec<Option<VersionCell>>)> {
+    let mut git_names: Vec<&str> = branch_modules
+        .iter()
+        .flat_map(|bm| &bm.modules)
+        .filter_map(|module| match module {
+            models::Module::Git(name, spec)
+                if include_git || spec.reference.version().is_some() =>
+            {
+                Some(name.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    git_names.sort();
+    git_names.dedup();
+    let mut rows = vec![];
+    for git_name in git_names {
+        let references: Vec<Option<&models::GitRef>> = branches
+            .iter()
+            .map(|bname| {
+                branch_modules
+                    .iter()
+                    .find(|bm| &bm.name == bname)
+                    .and_then(|bm| {
+                        bm.modules.iter().find_map(|module| match module {
+                            models::Module::Git(name, spec) if name == git_name => {
+                                Some(&spec.reference)
+                            }
+                            _ => None,
+                        })
+                    })
+            })
+            .collect();
+        let newest = references
+            .iter()
+            .filter_map(|reference| reference.and_then(models::GitRef::version))
+            .max();
+        // only used by branches that are not shown, or not with a semver tag
+        if references.iter().all(Option::is_none) || (newest.is_none() && !include_git) {
+            continue;
+        }
+        // the reference used by most branches, if there is one
+        let mut counts: Vec<(&models::GitRef, usize)> = vec![];
+        for reference in references.iter().flatten() {
+            match counts.iter_mut().find(|(r, _)| r == reference) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((reference, 1)),
+            }
+        }
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let common = match counts.as_slice() {
+            [(reference, _)] => Some(*reference),
+            [(reference, first), (_, second), ..] if first > second => Some(*reference),
+            // a tie, no reference is the common one
+            _ => None,
+        };
+        // distinguishes the git modules from the Forge modules
+        let mut version_cells = vec![Some(VersionCell {
+            text: "git".to_string(),
+            status: None,
+        })];
+        for (reference, totals) in references.iter().zip(branch_totals.iter_mut()) {
+            version_cells.push(reference.map(|reference| {
+                match (reference.version(), &newest) {
+                    (Some(version), Some(newest)) => {
+                        let status = if version.cmp_precedence(newest).is_lt() {
+                            totals.1 += 1;
+                            Status::Behind
+                        } else {
+                            totals.0 += 1;
+                            Status::Current
+                        };
+                        VersionCell {
+                            text: version.to_string(),
+                            status: Some(status),
+                        }
+                    }
+                    // pinned to a branch or commit while other branches use tags, nothing to
+                    // compare
+                    (_, Some(_)) => VersionCell {
+                        text: reference.to_string(),
+                        status: None,
+                    },
+                    (_, None) => VersionCell {
+                        text: reference.to_string(),
+                        status: (common != Some(reference)).then_some(Status::Differs),
+                    },
+                }
+            }));
+        }
+        rows.push((git_name.to_string(), version_cells));
+    }
+    rows
+}
+
+/// A requirement of a module on one of its dependencies
+#[derive(Debug)]
+pub struct Requirement {
+    /// The module that has the dependency, and its version
+    pub module: String,
+    pub version: Version,
+    /// The requirement as written, e.g. `>= 4.13.1 < 10.0.0`
+    pub text: String,
+    pub parsed: semver::VersionReq,
+}
+
+/// A dependency the Forge modules of a branch disagree on
+#[derive(Debug)]
+pub struct Conflict {
+    pub dependency: String,
+    /// The version of the dependency the branch uses, `None` if it does not use it
+    pub used: Option<Version>,
+    /// The requirements `used` does not meet. If the branch does not use the dependency, all of
+    /// them, as no release meets them all.
+    pub required_by: Vec<Requirement>,
+}
+
+/// Finds the dependencies the Forge modules of `branch` disagree on, by looking up the
+/// dependencies of each module and, for a dependency the branch does not use, its releases
+pub fn conflicts(api: &forge::ForgeApi, branch: &models::BranchMeta) -> Vec<Conflict> {
+    let versions: HashMap<&str, &Version> = branch
+        .modules
+        .iter()
+        .filter_map(|module| match module {
+            models::Module::Forge(name, version) => Some((name.as_str(), version)),
+            models::Module::Git(..) => None,
+        })
+        .collect();
+    // dependency → the requirements of every module in the branch
+    let mut requirements: HashMap<String, Vec<Requirement>> = HashMap::new();
+    for (name, version) in &versions {
+        let dependencies = match api.get_dependencies(name, version) {
+            Ok(d) => d,
+            Err(e) => {
+                warn!("Could not get the dependencies of {name} {version}: {e}");
+                continue;
+            }
+        };
+        for dependency in dependencies {
+            let text = dependency.version_requirement.unwrap_or_default();
+            match forge::parse_version_requirement(&text) {
+                Ok(parsed) => requirements
+                    .entry(dependency.name)
+                    .or_default()
+                    .push(Requirement {
+                        module: name.to_string(),
+                        version: (*version).clone(),
+                        text,
+                        parsed,
+                    }),
+                Err(e) => warn!(
+                    "{name} {version} has an invalid requirement '{text}' for {}: {e}",
+                    dependency.name
+                ),
+            }
+        }
+    }
+    requirement_conflicts(&versions, requirements, |dependency| {
+        api.get_releases(dependency)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|release| Version::parse(&release.version).ok())
+            .collect()
+    })
+}
+
+/// Checks the `requirements` on each dependency against the `versions` of a branch, sorted by
+/// dependency. `releases` returns the releases of a dependency the branch does not use.
+pub fn requirement_conflicts(
+    versions: &HashMap<&str, &Version>,
+    requirements: HashMap<String, Vec<Requirement>>,
+    releases: impl Fn(&str) -> Vec<Version>,
+) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    for (dependency, required_by) in requirements {
+        match versions.get(dependency.as_str()) {
+            // the branch decides which version is deployed, it has to satisfy everyone
+            Some(&used) => {
+                let unmet: Vec<Requirement> = required_by
+                    .into_iter()
+                    .filter(|requirement| !requirement.parsed.matches(used))
+                    .collect();
+                if !unmet.is_empty() {
+                    conflicts.push(Conflict {
+                        dependency,
+                        used: Some(used.clone()),
+                        required_by: unmet,
+                    });
+                }
+            }
+            // not in the branch (which is a problem of its own), but see if there is a release
+            // everyone would be happy with
+            None if required_by.len() > 1 => {
+                let satisfiable = releases(&dependency).iter().any(|version| {
+                    required_by
+                        .iter()
+                        .all(|requirement| requirement.parsed.matches(version))
+                });
+                if !satisfiable {
+                    conflicts.push(Conflict {
+                        dependency,
+                        used: None,
+                        required_by,
+                    });
+                }
+            }
+            None => (),
+        }
+    }
+    conflicts.sort_by(|a, b| a.dependency.cmp(&b.dependency));
+    conflicts
+}
+
+/// Finds the modules used by only one of `branches`, candidates for removal. Returns each of them
+/// along with the branch using it, sorted by name.
+pub fn orphans<'a>(branches: &[&'a models::BranchMeta]) -> Vec<(&'a models::Module, &'a str)> {
+    // module name → the module of every branch that uses it
+    let mut usage: HashMap<&str, Vec<(&models::Module, &str)>> = HashMap::new();
+    for branch in branches {
+        for module in &branch.modules {
+            let (models::Module::Forge(name, _) | models::Module::Git(name, _)) = module;
+            usage
+                .entry(name.as_str())
+                .or_default()
+                .push((module, branch.name.as_str()));
+        }
+    }
+    let mut orphans: Vec<(&models::Module, &str)> = usage
+        .into_values()
+        .filter(|branches| branches.len() == 1)
+        .map(|mut branches| branches.remove(0))
+        .collect();
+    orphans.sort_by_key(|(module, branch)| {
+        let (models::Module::Forge(name, _) | models::Module::Git(name, _)) = module;
+        (name.as_str(), *branch)
+    });
+    orphans
+}
+
+/// A deprecated module of the deprecated view
+pub struct DeprecatedModule<'a> {
+    pub name: &'a str,
+    pub deprecation: forge::Deprecation,
+    /// The branches using the module
+    pub branches: Vec<&'a str>,
+}
+
+/// Finds the deprecated modules among `names` that are used by any of `branches`, in the order of
+/// `names`
+pub fn deprecated_modules<'a>(
+    api: &forge::ForgeApi,
+    names: &'a [String],
+    branches: &[&'a models::BranchMeta],
+) -> Vec<DeprecatedModule<'a>> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let deprecation = lookup_or_warn("deprecation", name, api.get_deprecation(name))?;
+            let branches: Vec<&str> = branches
+                .iter()
+                .filter(|bm| {
+                    bm.modules
+                        .iter()
+                        .any(|module| matches!(module, models::Module::Forge(n, _) if n == name))
+                })
+                .map(|bm| bm.name.as_str())
+                .collect();
+            (!branches.is_empty()).then_some(DeprecatedModule {
+                name,
+                deprecation,
+                branches,
+            })
+        })
+        .collect()
+}
+
+/// Lists the git modules of `branches` for the git-modules view: the branch (without `prefix`),
+/// the module's name, its URL, the reference it is pinned to and the directory it is deployed to
+pub fn git_modules(branches: &[&models::BranchMeta], prefix: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    for branch in branches {
+        let short_name = repo::short_branch_name(&branch.name, prefix);
+        for module in &branch.modules {
+            let models::Module::Git(name, spec) = module else {
+                continue;
+            };
+            let reference = match spec.reference {
+                // resolve it, this is what is deployed for this environment
+                models::GitRef::ControlBranch => format!("control branch ({short_name})"),
+                ref reference => reference.to_string(),
+            };
+            rows.push(vec![
+                short_name.to_string(),
+                name.clone(),
+                spec.url.clone().unwrap_or_default(),
+                reference,
+                branch.install_path(module),
+            ]);
+        }
+    }
+    rows
+}
+
+/// A dependency of a module on another one, as drawn by the graph view
+pub struct Edge {
+    pub module: String,
+    pub dependency: String,
+    /// The requirement as written, empty if the module allows any version
+    pub requirement: String,
+    /// The branch uses a version of the dependency that does not meet the requirement
+    pub unmet: bool,
+}
+
+/// The dependencies between the Forge modules of a branch, for the graph view
+pub struct DependencyGraph<'a> {
+    /// The Forge modules of the branch with their version and whether they are deprecated
+    pub modules: Vec<(&'a str, &'a Version, bool)>,
+    pub edges: Vec<Edge>,
+    /// Dependencies that are not part of the branch
+    pub missing: Vec<String>,
+    /// Modules that could not be looked up, they are drawn without their dependencies
+    pub failed: Vec<(String, forge::ForgeError)>,
+}
+
+/// Looks up the dependencies between the Forge modules of `branch`. Modules that are not on the
+/// Forge are drawn without dependencies, other failed look-ups are collected in
+/// `DependencyGraph::failed`. With `fail_fast`, the first of them is returned as the error instead.
+pub fn dependency_graph<'a>(
+    api: &forge::ForgeApi,
+    branch: &'a models::BranchMeta,
+    fail_fast: bool,
+) -> Result<DependencyGraph<'a>, (String, forge::ForgeError)> {
+    let mut failed: Vec<(String, forge::ForgeError)> = vec![];
+    // the same way the other views handle modules that can't be looked up
+    let mut lookup = |name: &str, e: forge::ForgeError| match e {
+        forge::ForgeError::NotFound(_) => {
+            warn!("{name} was not found on the Forge, drawing it without dependencies");
+            Ok(())
+        }
+        e if fail_fast => Err((name.to_string(), e)),
+        e => {
+            warn!("Could not look up {name}, going on without it: {e}");
+            if !failed.iter().any(|(failed_name, _)| failed_name == name) {
+                failed.push((name.to_string(), e));
+            }
+            Ok(())
+        }
+    };
+    let mut modules: Vec<(&str, &Version, bool)> = vec![];
+    for module in &branch.modules {
+        let models::Module::Forge(name, version) = module else {
+            continue;
+        };
+        let deprecated = match api.is_deprecated(name) {
+            Ok(deprecated) => deprecated,
+            Err(e) => {
+                lookup(name, e)?;
+                false
+            }
+        };
+        modules.push((name.as_str(), version, deprecated));
+    }
+    let mut edges = vec![];
+    let mut missing: Vec<String> = vec![];
+    for (name, version, _) in &modules {
+        let dependencies = match api.get_dependencies(name, version) {
+            Ok(d) => d,
+            Err(e) => {
+                lookup(name, e)?;
+                continue;
+            }
+        };
+        for dependency in dependencies {
+            let requirement = dependency.version_requirement.unwrap_or_default();
+            let used = modules.iter().find(|(n, _, _)| *n == dependency.name);
+            // a requirement the branch does not meet is what to look at first
+            let unmet = match (used, forge::parse_version_requirement(&requirement)) {
+                (Some((_, used_version, _)), Ok(req)) => !req.matches(used_version),
+                _ => false,
+            };
+            if used.is_none() && !missing.contains(&dependency.name) {
+                missing.push(dependency.name.clone());
+            }
+            edges.push(Edge {
+                module: name.to_string(),
+                dependency: dependency.name,
+                requirement,
+                unmet,
+            });
+        }
+    }
+    Ok(DependencyGraph {
+        modules,
+        edges,
+        missing,
+        failed,
+    })
+}
+
+/// Updates the Forge modules of `branch` to their latest release, only the ones left in the branch
+/// (e.g. by `--module`) are updated
+pub fn bump_to_latest(
+    api: &forge::ForgeApi,
+    branch: &models::BranchMeta,
+) -> puppetfile::BumpedPuppetfile {
+    let in_scope: HashSet<&str> = branch
+        .modules
+        .iter()
+        .filter_map(|module| match module {
+            models::Module::Forge(name, _) => Some(name.as_str()),
+            models::Module::Git(..) => None,
+        })
+        .collect();
+    puppetfile::bump_forge_modules(&branch.puppetfile, |name, _| {
+        if !in_scope.contains(name) {
+            return None;
+        }
+        match api.get_version(name) {
+            Ok(version) => Some(version),
+            Err(e) => {
+                warn!("Could not get the latest version of {name}, not bumping it: {e}");
+                None
+            }
+        }
+    })
+}
+
+/// A module's latest version on the Forge (`None` if it has no releases) and whether it is
+/// deprecated, as compared by the drift view and `--changes-since`
+pub type ForgeState = (Option<Version>, bool);
+
+/// Builds the row of the module `name` for the drift view and `--changes-since`, `None` if nothing
+/// changed. A state is `None` if the module was not known at that time, `added` and `removed`
+/// describe it becoming known or unknown.
+pub fn change_row(
+    name: &str,
+    old: Option<ForgeState>,
+    new: Option<ForgeState>,
+    added: &str,
+    removed: &str,
+) -> Option<Vec<TableCell>> {
+    let mut changes = vec![];
+    match (&old, &new) {
+        (None, Some(_)) => changes.push(added),
+        (Some(_), None) => changes.push(removed),
+        (Some((old_version, _)), Some((new_version, _))) => match (old_version, new_version) {
+            (None, Some(_)) => changes.push("new release"),
+            (Some(_), None) => changes.push("release deleted"),
+            (Some(o), Some(n)) if n > o => changes.push("new release"),
+            (Some(o), Some(n)) if n < o => changes.push("release deleted"),
+            _ => (),
+        },
+        (None, None) => (),
+    }
+    let old_deprecated = old.as_ref().is_some_and(|(_, deprecated)| *deprecated);
+    let new_deprecated = new.as_ref().is_some_and(|(_, deprecated)| *deprecated);
+    match (old_deprecated, new_deprecated) {
+        (false, true) => changes.push("deprecated"),
+        (true, false) => changes.push("no longer deprecated"),
+        _ => (),
+    }
+    if changes.is_empty() {
+        return None;
+    }
+    let text = |version: &Option<Version>| {
+        version
+            .as_ref()
+            .map_or(NO_RELEASES.to_string(), Version::to_string)
+    };
+    let changed = old.as_ref().map(|(v, _)| v) != new.as_ref().map(|(v, _)| v);
+    Some(vec![
+        TableCell::Module(name.to_string()),
+        TableCell::Version(old.as_ref().map(|(version, _)| VersionCell {
+            text: text(version),
+            status: (changed && new.is_some()).then_some(Status::Behind),
+        })),
+        TableCell::Version(new.as_ref().map(|(version, deprecated)| VersionCell {
+            text: text(version),
+            status: Some(if *deprecated {
+                Status::Deprecated
+            } else {
+                Status::Current
+            }),
+        })),
+        TableCell::Text(changes.join(", ")),
+    ])
+}
+
+/// Builds the rows of the releases view, the version of each release of `releases` and, if
+/// `with_dates`, when it was published
+pub fn release_rows(releases: Vec<forge::ReleaseEntry>, with_dates: bool) -> Vec<Vec<String>> {
+    releases
+        .into_iter()
+        .map(|release| {
+            let mut row = vec![release.version];
+            if with_dates {
+                row.push(
+                    release
+                        .created_at
+                        .map(|date| date.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                );
+            }
+            row
+        })
+        .collect()
+}
+
+/// Builds the rows of the drift view, comparing the cache entries `old` with `new`. Modules that
+/// don't exist count as unknown.
+pub fn drift_rows(
+    old: &HashMap<String, forge::CacheEntry>,
+    new: &HashMap<String, forge::CacheEntry>,
+) -> Vec<Vec<TableCell>> {
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    let state = |entry: Option<&forge::CacheEntry>| {
+        entry
+            .filter(|e| !e.not_found)
+            .map(|e| (Version::parse(&e.version).ok(), e.is_deprecated))
+    };
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (old_state, new_state) = (state(old.get(name)), state(new.get(name)));
+            change_row(name, old_state, new_state, "added", "removed")
+        })
+        .collect()
+}
+
+/// Builds the rows of `--changes-since`, comparing the modules `names` in use now with the ones of
+/// `old_names` in use back then. `old_api` answers like the Forge did back then. The modules of
+/// `failed_names` could not be looked up, they are neither added nor removed.
+pub fn changes_since_rows(
+    api: &forge::ForgeApi,
+    old_api: &forge::ForgeApi,
+    names: &[String],
+    old_names: &HashSet<String>,
+    failed_names: &[String],
+) -> Vec<Vec<TableCell>> {
+    let old_state = |name: &str| -> Option<ForgeState> {
+        if !old_names.contains(name) {
+            return None;
+        }
+        let version = latest_version(old_api, name).ok()?;
+        Some((version, old_api.is_deprecated(name).ok()?))
+    };
+    let mut all_names: Vec<&String> = names
+        .iter()
+        .chain(old_names.iter().filter(|name| !failed_names.contains(name)))
+        .collect();
+    all_names.sort();
+    all_names.dedup();
+    all_names
+        .into_iter()
+        .filter_map(|name| {
+            let new_state = names.contains(name).then(|| {
+                (
+                    lookup_or_warn("latest version", name, latest_version(api, name)),
+                    lookup_or_warn("deprecation", name, api.is_deprecated(name)),
+                )
+            });
+            change_row(
+                name,
+                old_state(name),
+                new_state,
+                "now in use",
+                "no longer in use",
+            )
+        })
+        .collect()
+}
+
+/// The status of a module's latest release on the Forge, if it is worth pointing out
+pub fn latest_status(deprecated: bool, stale: bool) -> Option<Status> {
+    if deprecated {
+        Some(Status::Deprecated)
+    } else if stale {
+        Some(Status::Stale)
+    } else {
+        None
+    }
+}
+
+/// Key figures of the branches view for scripts, see `--summary-file`
+#[derive(Default, serde::Serialize)]
+pub struct Summary {
+    /// Forge modules used by the shown branches
+    pub modules: usize,
+    /// Modules that are older than the latest Forge release in at least one shown branch
+    pub outdated: usize,
+    /// Modules that are deprecated and used by at least one shown branch
+    pub deprecated: usize,
+    /// Number of `--fail-on` violations
+    pub violations: usize,
+    /// Whether the policies of `--fail-on` are met, i.e. the exit code is not 2
+    pub passed: bool,
+}
+
+/// How long the steps of a run took, for `--timings`
+#[derive(Default)]
+pub struct Timings {
+    /// In the order the steps were first taken
+    pub steps: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Adds `elapsed` to the time of `step`, which may be taken several times (e.g. once per
+    /// repository)
+    pub fn add(&mut self, step: &'static str, elapsed: Duration) {
+        match self.steps.iter_mut().find(|(name, _)| *name == step) {
+            Some((_, total)) => *total += elapsed,
+            None => self.steps.push((step, elapsed)),
+        }
+    }
+}
+
+/// A cell of the branches table, which can be transposed
+#[derive(Clone)]
+pub enum TableCell {
+    /// Plain text, like a branch's name
+    Text(String),
+    /// A module's name, linked to its page on the Forge
+    Module(String),
+    /// A version, empty if the module is not used
+    Version(Option<VersionCell>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branch(name: &str, puppetfile: &str) -> models::BranchMeta {
+        models::BranchMeta {
+            name: name.to_string(),
+            oid: git2::Oid::zero(),
+            author_date: DateTime::default(),
+            commit_date: DateTime::default(),
+            author: String::new(),
+            puppetfile: puppetfile.to_string(),
+            moduledir: models::DEFAULT_MODULEDIR.to_string(),
+            modules: crate::puppetfile::parse_puppetfile(puppetfile).modules,
+        }
+    }
+
+    /// The branches `origin/dev`, `origin/live` and `origin/qa`
+    fn branches() -> Vec<models::BranchMeta> {
+        vec![
+            branch(
+                "origin/dev",
+                "mod 'puppetlabs/stdlib', '9.7.0'
+mod 'profile', :git => 'https://git.example.com/profile.git', :tag => 'v1.2.0'
+mod 'role', :git => 'https://git.example.com/role.git', :branch => 'main'",
+            ),
+            branch(
+                "origin/live",
+                "mod 'puppetlabs/stdlib', '8.5.0'
+mod 'puppet/systemd', '8.1.0'
+mod 'profile', :git => 'https://git.example.com/profile.git', :tag => 'v1.1.0'
+mod 'role', :git => 'https://git.example.com/role.git', :branch => 'live'",
+            ),
+            branch(
+                "origin/qa",
+                "mod 'puppetlabs/stdlib', '9.6.0'
+mod 'role', :git => 'https://git.example.com/role.git', :branch => 'main'",
+            ),
+        ]
+    }
+
+    fn shown() -> Vec<String> {
+        ["origin/dev", "origin/live", "origin/qa"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn stdlib() -> ModuleRow {
+        let mut row = ModuleRow::new("puppetlabs-stdlib", &branches());
+        row.forge_version = Some(Version::new(9, 7, 0));
+        row
+    }
+
+    fn cell_text(cell: &Option<VersionCell>) -> Option<(&str, Option<Status>)> {
+        cell.as_ref().map(|cell| (cell.text.as_str(), cell.status))
+    }
+
+    #[test]
+    fn module_row() {
+        let row = stdlib();
+        assert_eq!(row.max_in_use_version, Version::new(9, 7, 0));
+        assert_eq!(row.min_in_use_version, Some(Version::new(8, 5, 0)));
+        assert_eq!(row.branch_versions.len(), 3);
+        assert_eq!(
+            row.lag(),
+            models::VersionLag {
+                major: 1,
+                minor: 0,
+                patch: 0
+            }
+        );
+        // no branch uses a newer version than the latest release
+        let cell = row.forge_cell();
+        assert_eq!((cell.text.as_str(), cell.status), ("9.7.0", None));
+
+        let systemd = ModuleRow::new("puppet-systemd", &branches());
+        assert_eq!(systemd.branch_versions.len(), 1);
+        assert_eq!(systemd.lag(), models::VersionLag::default());
+        assert_eq!(systemd.forge_cell().text, NO_RELEASES);
+        assert!(systemd
+            .branch_cell("origin/live", Baseline::Forge, None, false)
+            .is_some_and(|(cell, outdated)| cell.status.is_none() && !outdated));
+    }
+
+    #[test]
+    fn branch_cell() {
+        let mut row = stdlib();
+        let cell = |row: &ModuleRow, branch, baseline, minimum, show_target| {
+            row.branch_cell(branch, baseline, minimum, show_target)
+                .map(|(cell, outdated)| (cell.text, cell.status, outdated))
+        };
+        assert_eq!(
+            cell(&row, "origin/live", Baseline::Forge, None, true),
+            Some(("8.5.0 (→9.7.0)".to_string(), Some(Status::Behind), true))
+        );
+        assert_eq!(
+            cell(&row, "origin/dev", Baseline::Forge, None, true),
+            Some(("9.7.0".to_string(), Some(Status::Current), false))
+        );
+        assert_eq!(cell(&row, "origin/nope", Baseline::Forge, None, true), None);
+        let minimum = Version::new(9, 7, 0);
+        assert_eq!(
+            cell(&row, "origin/qa", Baseline::MaxInUse, Some(&minimum), false),
+            Some(("9.6.0".to_string(), Some(Status::BelowMinimum), true))
+        );
+
+        // even the latest release of a deprecated module is outdated
+        row.forge_version = Some(Version::new(9, 0, 0));
+        row.forge_deprecated = true;
+        assert_eq!(
+            cell(&row, "origin/qa", Baseline::Forge, None, false),
+            Some(("9.6.0".to_string(), Some(Status::Ahead), true))
+        );
+        assert_eq!(row.forge_cell().status, Some(Status::Deprecated));
+        row.forge_deprecated = false;
+        row.forge_version = Some(Version::new(10, 0, 0));
+        assert_eq!(row.forge_cell().status, Some(Status::Behind));
+        row.lookup_failed = true;
+        assert_eq!(row.forge_cell().text, LOOKUP_FAILED);
+    }
+
+    #[test]
+    fn branch_cells_totals() {
+        let minimum = Version::new(9, 7, 0);
+        let min_versions = HashMap::from([("puppetlabs-stdlib", &minimum)]);
+        let (cells, totals) = branch_cells(
+            vec![stdlib()],
+            &shown(),
+            Baseline::Forge,
+            &min_versions,
+            false,
+        );
+        assert_eq!(cells.len(), 1);
+        let (name, cells) = &cells[0];
+        assert!(matches!(name, TableCell::Module(name) if name == "puppetlabs-stdlib"));
+        let cells: Vec<_> = cells.iter().map(cell_text).collect();
+        assert_eq!(
+            cells,
+            [
+                Some(("9.7.0", None)),
+                Some(("9.7.0", Some(Status::Current))),
+                Some(("8.5.0", Some(Status::BelowMinimum))),
+                Some(("9.6.0", Some(Status::BelowMinimum))),
+            ]
+        );
+        assert_eq!(totals, [(1, 0), (0, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn policies() {
+        let mut systemd = ModuleRow::new("puppet-systemd", &branches());
+        systemd.forge_version = Some(Version::new(8, 1, 0));
+        systemd.forge_deprecated = true;
+        let rows = [stdlib(), systemd];
+        let minimum = Version::new(9, 7, 0);
+        let policy = Policy {
+            deprecated: true,
+            max_behind: Some(models::VersionLag {
+                major: 0,
+                minor: 5,
+                patch: 0,
+            }),
+            below_minimum: true,
+            min_versions: HashMap::from([("puppetlabs-stdlib", &minimum)]),
+        };
+        let (figures, violations) = check_policies(&rows, &shown(), "origin/", &policy);
+        assert_eq!(
+            (figures.modules, figures.outdated, figures.deprecated),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            violations,
+            [
+                "puppetlabs-stdlib is at 8.5.0 in branch live, but 9.7.0 is available",
+                "puppetlabs-stdlib is at 8.5.0 in branch live, but at least 9.7.0 is required",
+                "puppetlabs-stdlib is at 9.6.0 in branch qa, but at least 9.7.0 is required",
+                "puppet-systemd is deprecated but used in branch live",
+            ]
+        );
+
+        // only the shown branches count
+        let (figures, violations) = check_policies(
+            &rows,
+            &["origin/dev".to_string()],
+            "origin/",
+            &Policy::default(),
+        );
+        assert_eq!(
+            (figures.modules, figures.outdated, figures.deprecated),
+            (1, 0, 0)
+        );
+        assert!(violations.is_empty());
+    }
+
+    #[test]
+    fn max_behind() {
+        let violations = |major, minor| {
+            let policy = Policy {
+                max_behind: Some(models::VersionLag {
+                    major,
+                    minor,
+                    patch: 0,
+                }),
+                ..Policy::default()
+            };
+            check_policies(&[stdlib()], &shown(), "origin/", &policy).1
+        };
+        // `qa` is one minor release behind, `live` one major release
+        assert_eq!(
+            violations(0, 0),
+            [
+                "puppetlabs-stdlib is at 8.5.0 in branch live, but 9.7.0 is available",
+                "puppetlabs-stdlib is at 9.6.0 in branch qa, but 9.7.0 is available",
+            ]
+        );
+        assert_eq!(
+            violations(0, 1),
+            ["puppetlabs-stdlib is at 8.5.0 in branch live, but 9.7.0 is available"]
+        );
+        // only a lag above the threshold is a violation
+        assert!(violations(1, 0).is_empty());
+    }
+
+    #[test]
+    fn git_module_rows() {
+        let mut totals = vec![(0, 0); 3];
+        let rows = git_rows(&branches(), &shown(), false, &mut totals);
+        assert_eq!(rows.len(), 1);
+        let (name, cells) = &rows[0];
+        assert_eq!(name, "profile");
+        let cells: Vec<_> = cells.iter().map(cell_text).collect();
+        assert_eq!(
+            cells,
+            [
+                Some(("git", None)),
+                Some(("1.2.0", Some(Status::Current))),
+                Some(("1.1.0", Some(Status::Behind))),
+                None,
+            ]
+        );
+        assert_eq!(totals, [(1, 0), (0, 1), (0, 0)]);
+
+        // the branches are compared with the reference most of them use
+        let rows = git_rows(&branches(), &shown(), true, &mut totals);
+        let (name, cells) = &rows[1];
+        assert_eq!(name, "role");
+        let cells: Vec<_> = cells.iter().map(cell_text).collect();
+        assert_eq!(
+            cells,
+            [
+                Some(("git", None)),
+                Some(("branch main", None)),
+                Some(("branch live", Some(Status::Differs))),
+                Some(("branch main", None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn git_module_list() {
+        let branches = branches();
+        let branches: Vec<&models::BranchMeta> = branches.iter().skip(1).collect();
+        assert_eq!(
+            git_modules(&branches, "origin/"),
+            [
+                [
+                    "live",
+                    "profile",
+                    "https://git.example.com/profile.git",
+                    "tag v1.1.0",
+                    "modules/profile",
+                ],
+                [
+                    "live",
+                    "role",
+                    "https://git.example.com/role.git",
+                    "branch live",
+                    "modules/role",
+                ],
+                [
+                    "qa",
+                    "role",
+                    "https://git.example.com/role.git",
+                    "branch main",
+                    "modules/role",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn drift() {
+        let entry = |version: &str, is_deprecated: bool| -> forge::CacheEntry {
+            serde_json::from_value(serde_json::json!({
+                "version": version,
+                "is_deprecated": is_deprecated,
+                "time_fetched": 0,
+            }))
+            .unwrap()
+        };
+        let old = HashMap::from([
+            ("a".to_string(), entry("1.0.0", false)),
+            ("b".to_string(), entry("2.0.0", false)),
+            ("c".to_string(), entry("3.0.0", false)),
+        ]);
+        let new = HashMap::from([
+            ("a".to_string(), entry("1.0.0", false)),
+            ("b".to_string(), entry("2.0.0", true)),
+            ("d".to_string(), entry("4.0.0", false)),
+        ]);
+        let names: Vec<String> = drift_rows(&old, &new)
+            .into_iter()
+            .map(|row| match &row[0] {
+                TableCell::Text(name) | TableCell::Module(name) => name.clone(),
+                TableCell::Version(_) => unreachable!(),
+            })
+            .collect();
+        // `a` did not change
+        assert_eq!(names, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn changes() {
+        let texts = |row: Option<Vec<TableCell>>| -> Option<Vec<String>> {
+            row.map(|cells| {
+                cells
+                    .into_iter()
+                    .map(|cell| match cell {
+                        TableCell::Text(text) | TableCell::Module(text) => text,
+                        TableCell::Version(version) => version.map(|v| v.text).unwrap_or_default(),
+                    })
+                    .collect()
+            })
+        };
+        let old = Some((Some(Version::new(1, 0, 0)), false));
+        assert_eq!(
+            texts(change_row("a", old.clone(), old.clone(), "+", "-")),
+            None
+        );
+        assert_eq!(
+            texts(change_row(
+                "a",
+                old.clone(),
+                Some((Some(Version::new(1, 1, 0)), true)),
+                "+",
+                "-"
+            )),
+            Some(
+                ["a", "1.0.0", "1.1.0", "new release, deprecated"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            texts(change_row("a", old.clone(), Some((None, false)), "+", "-")),
+            Some(
+                ["a", "1.0.0", NO_RELEASES, "release deleted"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            texts(change_row("a", None, old.clone(), "added", "removed")),
+            Some(["a", "", "1.0.0", "added"].map(String::from).to_vec())
+        );
+        assert_eq!(
+            texts(change_row("a", old, None, "added", "removed")),
+            Some(["a", "1.0.0", "", "removed"].map(String::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn orphan_modules() {
+        let branches = branches();
+        let branches: Vec<&models::BranchMeta> = branches.iter().collect();
+        let names = |orphans: Vec<(&models::Module, &str)>| -> Vec<(String, String)> {
+            orphans
+                .into_iter()
+                .map(|(module, branch)| {
+                    let (models::Module::Forge(name, _) | models::Module::Git(name, _)) = module;
+                    (name.clone(), branch.to_string())
+                })
+                .collect()
+        };
+        assert_eq!(
+            names(orphans(&branches)),
+            [("puppet-systemd".to_string(), "origin/live".to_string())]
+        );
+        // `profile` is used by `origin/dev` as well
+        assert_eq!(
+            names(orphans(&branches[1..])),
+            [
+                ("profile".to_string(), "origin/live".to_string()),
+                ("puppet-systemd".to_string(), "origin/live".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn requirements() {
+        let requirement = |module: &str, text: &str| Requirement {
+            module: module.to_string(),
+            version: Version::new(1, 0, 0),
+            text: text.to_string(),
+            parsed: forge::parse_version_requirement(text).unwrap(),
+        };
+        let stdlib = Version::new(8, 5, 0);
+        let versions = HashMap::from([("puppetlabs-stdlib", &stdlib)]);
+        let requirements = HashMap::from([
+            (
+                "puppetlabs-stdlib".to_string(),
+                vec![
+                    requirement("puppet-systemd", ">= 9.0.0 < 10.0.0"),
+                    requirement("saz-timezone", ">= 4.13.1 < 10.0.0"),
+                ],
+            ),
+            (
+                "puppetlabs-concat".to_string(),
+                vec![
+                    requirement("puppet-systemd", ">= 7.0.0 < 8.0.0"),
+                    requirement("saz-timezone", ">= 8.0.0 < 10.0.0"),
+                ],
+            ),
+            (
+                "puppetlabs-inifile".to_string(),
+                vec![
+                    requirement("puppet-systemd", ">= 5.0.0 < 7.0.0"),
+                    requirement("saz-timezone", ">= 6.0.0 < 7.0.0"),
+                ],
+            ),
+            (
+                "puppet-archive".to_string(),
+                vec![requirement("puppet-systemd", ">= 7.0.0")],
+            ),
+        ]);
+        let conflicts =
+            requirement_conflicts(&versions, requirements, |dependency| match dependency {
+                "puppetlabs-inifile" => vec![Version::new(6, 1, 0)],
+                _ => vec![Version::new(7, 2, 0), Version::new(8, 0, 0)],
+            });
+        let conflicts: Vec<(&str, Option<&Version>, Vec<&str>)> = conflicts
+            .iter()
+            .map(|conflict| {
+                (
+                    conflict.dependency.as_str(),
+                    conflict.used.as_ref(),
+                    conflict
+                        .required_by
+                        .iter()
+                        .map(|requirement| requirement.module.as_str())
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            conflicts,
+            [
+                (
+                    "puppetlabs-concat",
+                    None,
+                    vec!["puppet-systemd", "saz-timezone"]
+                ),
+                ("puppetlabs-stdlib", Some(&stdlib), vec!["puppet-systemd"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn timings() {
+        let mut timings = Timings::default();
+        timings.add("Parsing", Duration::from_secs(1));
+        timings.add("Looking up", Duration::from_secs(2));
+        timings.add("Parsing", Duration::from_secs(3));
+        assert_eq!(
+            timings.steps,
+            [
+                ("Parsing", Duration::from_secs(4)),
+                ("Looking up", Duration::from_secs(2))
+            ]
+        );
+    }
+}
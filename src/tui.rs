@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Interactive table for browsing the branches view in the terminal (`--tui`)
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::io;
+
+use crate::{Palette, TableCell};
+
+/// Lines shown for a module when pressing enter on it, as (label, value)
+pub type Details = Vec<(&'static str, String)>;
+
+/// State of the interactive table
+struct Browser<'a> {
+    header: &'a [TableCell],
+    rows: &'a [Vec<TableCell>],
+    footer: Option<&'a [TableCell]>,
+    details: &'a HashMap<String, Details>,
+    palette: &'a Palette,
+    /// Typed text, rows whose first cell doesn't contain it are hidden
+    filter: String,
+    /// Indices of the rows matching the filter
+    visible: Vec<usize>,
+    state: TableState,
+    /// Number of columns scrolled to the right, the first column always stays
+    column_offset: usize,
+    show_details: bool,
+}
+
+/// Shows the table until the user quits. The first column names the row, it is what the filter
+/// applies to and what `details` are looked up by.
+pub fn browse(
+    header: &[TableCell],
+    rows: &[Vec<TableCell>],
+    footer: Option<&[TableCell]>,
+    details: &HashMap<String, Details>,
+    palette: &Palette,
+) -> io::Result<()> {
+    let mut browser = Browser {
+        header,
+        rows,
+        footer,
+        details,
+        palette,
+        filter: String::new(),
+        visible: (0..rows.len()).collect(),
+        state: TableState::default().with_selected((!rows.is_empty()).then_some(0)),
+        column_offset: 0,
+        show_details: false,
+    };
+    let mut terminal = ratatui::try_init()?;
+    let result = browser.run(&mut terminal);
+    // leave the terminal usable even if drawing failed
+    ratatui::restore();
+    result
+}
+
+/// Text of a cell without any decoration
+fn cell_text(cell: &TableCell) -> &str {
+    match cell {
+        TableCell::Text(text) | TableCell::Module(text) => text,
+        TableCell::Version(version) => version.as_ref().map_or("", |v| v.text.as_str()),
+    }
+}
+
+/// Translates the colours of the printed tables, which come in light and dark variants
+fn color(color: comfy_table::Color) -> Color {
+    use comfy_table::Color as C;
+    match color {
+        C::Reset => Color::Reset,
+        C::Black => Color::Black,
+        C::DarkGrey => Color::DarkGray,
+        C::Red => Color::LightRed,
+        C::DarkRed => Color::Red,
+        C::Green => Color::LightGreen,
+        C::DarkGreen => Color::Green,
+        C::Yellow => Color::LightYellow,
+        C::DarkYellow => Color::Yellow,
+        C::Blue => Color::LightBlue,
+        C::DarkBlue => Color::Blue,
+        C::Magenta => Color::LightMagenta,
+        C::DarkMagenta => Color::Magenta,
+        C::Cyan => Color::LightCyan,
+        C::DarkCyan => Color::Cyan,
+        C::White => Color::White,
+        C::Grey => Color::Gray,
+        C::Rgb { r, g, b } => Color::Rgb(r, g, b),
+        C::AnsiValue(value) => Color::Indexed(value),
+    }
+}
+
+/// A cell in the colour of its version's status
+fn styled_cell<'a>(palette: &Palette, cell: &'a TableCell) -> Cell<'a> {
+    match cell {
+        TableCell::Version(Some(version)) => {
+            match version.status.and_then(|status| palette.color(status)) {
+                Some(status_color) => Cell::from(version.text.as_str()).fg(color(status_color)),
+                None => Cell::from(version.text.as_str()),
+            }
+        }
+        _ => Cell::from(cell_text(cell)),
+    }
+}
+
+impl<'a> Browser<'a> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                KeyCode::Esc if self.show_details => self.show_details = false,
+                KeyCode::Esc if !self.filter.is_empty() => self.set_filter(String::new()),
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Enter => {
+                    self.show_details = !self.show_details && self.selected_details().is_some()
+                }
+                KeyCode::Up => self.state.select_previous(),
+                KeyCode::Down => self.state.select_next(),
+                KeyCode::PageUp => self.state.scroll_up_by(10),
+                KeyCode::PageDown => self.state.scroll_down_by(10),
+                KeyCode::Home => self.state.select_first(),
+                KeyCode::End => self.state.select_last(),
+                KeyCode::Left => self.column_offset = self.column_offset.saturating_sub(1),
+                KeyCode::Right => {
+                    let scrollable = self.header.len().saturating_sub(2);
+                    self.column_offset = (self.column_offset + 1).min(scrollable);
+                }
+                KeyCode::Backspace => {
+                    let mut filter = self.filter.clone();
+                    filter.pop();
+                    self.set_filter(filter);
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    let mut filter = self.filter.clone();
+                    filter.push(c);
+                    self.set_filter(filter);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        let needle = filter.to_lowercase();
+        self.visible = (0..self.rows.len())
+            .filter(|&idx| {
+                self.rows[idx]
+                    .first()
+                    .is_some_and(|cell| cell_text(cell).to_lowercase().contains(&needle))
+            })
+            .collect();
+        self.filter = filter;
+        self.state.select((!self.visible.is_empty()).then_some(0));
+        self.show_details = false;
+    }
+
+    /// The name and details of the selected row, if there are any
+    fn selected_details(&self) -> Option<(&str, &Details)> {
+        let row = &self.rows[*self.visible.get(self.state.selected()?)?];
+        let name = cell_text(row.first()?);
+        self.details.get(name).map(|details| (name, details))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        // the first column, followed by the ones scrolled to
+        let columns: Vec<usize> = std::iter::once(0)
+            .chain((1 + self.column_offset)..self.header.len())
+            .collect();
+        let widths: Vec<Constraint> = columns
+            .iter()
+            .map(|&col| {
+                let width = std::iter::once(self.header)
+                    .chain(self.footer)
+                    .chain(self.rows.iter().map(Vec::as_slice))
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| cell_text(cell).chars().count())
+                    .max()
+                    .unwrap_or_default();
+                Constraint::Length(width as u16)
+            })
+            .collect();
+        let row = |cells: &'a [TableCell]| -> Vec<Cell<'a>> {
+            columns
+                .iter()
+                .filter_map(|&col| cells.get(col))
+                .map(|cell| styled_cell(self.palette, cell))
+                .collect()
+        };
+        let rows = self
+            .visible
+            .iter()
+            .map(|&idx| Row::new(row(&self.rows[idx])));
+        let mut table = Table::new(rows, widths)
+            .header(Row::new(row(self.header)).bold())
+            .row_highlight_style(Style::new().reversed())
+            .block(Block::bordered());
+        if let Some(footer) = self.footer {
+            table = table.footer(Row::new(row(footer)).bold());
+        }
+        frame.render_stateful_widget(table, table_area, &mut self.state);
+
+        let status = Line::from(vec![
+            Span::from(" Filter: ").bold(),
+            Span::from(format!("{}_ ", self.filter)),
+            Span::from(format!("({}/{}) ", self.visible.len(), self.rows.len())),
+            Span::from("↑↓ select, ←→ scroll, type to filter, Enter details, Esc quit").dim(),
+        ]);
+        frame.render_widget(status, status_area);
+
+        if self.show_details {
+            if let Some((name, details)) = self.selected_details() {
+                let lines: Vec<Line> = details
+                    .iter()
+                    .map(|(label, value)| {
+                        Line::from(vec![
+                            Span::from(format!("{label}: ")).bold(),
+                            Span::from(value.as_str()),
+                        ])
+                    })
+                    .collect();
+                let area = popup_area(table_area, lines.len() as u16 + 2);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(lines).block(Block::bordered().title(name)),
+                    area,
+                );
+            }
+        }
+    }
+}
+
+/// A box of `height` lines in the middle of `area`
+fn popup_area(area: Rect, height: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(60)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    area
+}